@@ -4,7 +4,7 @@ use std::fs;
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
 use relay_general::pii::{DataScrubbingConfig, PiiProcessor};
-use relay_general::processor::{process_value, SelectorSpec};
+use relay_general::processor::{process_value, ProcessingState, SelectorSpec, ValueType};
 use relay_general::protocol::{Event, IpAddr};
 use relay_general::store::{StoreConfig, StoreProcessor};
 use relay_general::types::Annotated;
@@ -188,6 +188,30 @@ fn bench_parse_pii_selector(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_match_pii_selector(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_match_pii_selector");
+
+    let event_state = ProcessingState::new_root(None, Some(ValueType::Event));
+    let user_state = event_state.enter_static("user", None, Some(ValueType::User));
+    let extra_state = user_state.enter_static("extra", None, Some(ValueType::Object));
+    let foo_state = extra_state.enter_static("foo", None, Some(ValueType::String));
+
+    let mut bench = |input: BenchmarkInput<&str>| {
+        let selector = input.data.parse::<SelectorSpec>().unwrap();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(&input),
+            &selector,
+            |b, selector| b.iter(|| foo_state.path().matches_selector(selector)),
+        );
+    };
+
+    bench(BenchmarkInput::new("literal", "user.extra.foo"));
+    bench(BenchmarkInput::new("wildcard", "user.*.foo"));
+    bench(BenchmarkInput::new("deep_wildcard", "user.**.foo"));
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_from_value,
@@ -195,5 +219,6 @@ criterion_group!(
     bench_store_processor,
     bench_pii_stripping,
     bench_parse_pii_selector,
+    bench_match_pii_selector,
 );
 criterion_main!(benches);
@@ -102,6 +102,8 @@ macro_rules! impl_str_serde {
 /// Implements FromStr and Display on a flat/C-like enum such that strings roundtrip correctly and
 /// all variants can be FromStr'd.
 ///
+/// Additionally, `as_str` returns the display name and `aliases` returns all names that are
+/// accepted by FromStr for a variant, starting with the display name.
 ///
 /// Usage:
 ///
@@ -129,6 +131,15 @@ macro_rules! derive_fromstr_and_display {
                     ),*
                 }
             }
+
+            #[allow(dead_code)] // not all private types need this
+            pub fn aliases(&self) -> &'static [&'static str] {
+                match *self {
+                    $(
+                        $variant => &[$($name),*]
+                    ),*
+                }
+            }
         }
 
         impl ::std::fmt::Display for $type {
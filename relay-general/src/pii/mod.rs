@@ -11,6 +11,7 @@ mod minidumps;
 mod processor;
 mod redactions;
 mod regexes;
+mod summary;
 mod utils;
 
 pub use self::attachments::{PiiAttachmentsProcessor, ScrubEncodings};
@@ -25,3 +26,4 @@ pub use self::legacy::DataScrubbingConfig;
 pub use self::minidumps::ScrubMinidumpError;
 pub use self::processor::PiiProcessor;
 pub use self::redactions::{Redaction, ReplaceRedaction};
+pub use self::summary::{RedactedField, RedactionSummary};
@@ -49,11 +49,14 @@ impl<'a> PiiProcessor<'a> {
         }
 
         for (selector, rules) in self.compiled_config.applications.iter() {
-            if pii == Pii::Maybe && !selector.is_specific() {
-                continue;
-            }
+            let matches = match value {
+                Some(ref value) => state.path().matches_value_selector(pii, selector, value),
+                None => {
+                    pii.effective(selector.is_specific()) && state.path().matches_selector(selector)
+                }
+            };
 
-            if state.path().matches_selector(selector) {
+            if matches {
                 for rule in rules {
                     let reborrowed_value = value.as_deref_mut();
                     apply_rule_to_value(meta, rule, state.path().key(), reborrowed_value)?;
@@ -947,3 +950,86 @@ fn test_ip_address_hashing_does_not_overwrite_id() {
 
     assert_eq!(user.id.value().unwrap().as_str(), "123");
 }
+
+#[test]
+fn test_value_selector() {
+    let config = PiiConfig::from_json(
+        r##"
+            {
+                "applications": {
+                    "$value('^[^@]+@[^@]+$')": ["@anything:remove"]
+                }
+            }
+        "##,
+    )
+    .unwrap();
+
+    let mut event = Annotated::new(Event {
+        extra: {
+            let mut map = Object::new();
+            map.insert(
+                "contact".to_string(),
+                Annotated::new(ExtraValue(Value::String("foo@example.com".to_string()))),
+            );
+            map.insert(
+                "name".to_string(),
+                Annotated::new(ExtraValue(Value::String("foo".to_string()))),
+            );
+            Annotated::new(map)
+        },
+        ..Default::default()
+    });
+
+    let compiled = config.compiled();
+    let mut processor = PiiProcessor::new(&compiled);
+    process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+    let extra = event.value().unwrap().extra.value().unwrap();
+    assert!(extra.get("contact").unwrap().value().is_none());
+    assert_eq!(
+        extra.get("name").unwrap().value(),
+        Some(&ExtraValue(Value::String("foo".to_string())))
+    );
+}
+
+#[test]
+fn test_negated_value_selector() {
+    let config = PiiConfig::from_json(
+        r##"
+            {
+                "applications": {
+                    "!$value('^[^@]+@[^@]+$')": ["@anything:remove"]
+                }
+            }
+        "##,
+    )
+    .unwrap();
+
+    let mut event = Annotated::new(Event {
+        extra: {
+            let mut map = Object::new();
+            map.insert(
+                "contact".to_string(),
+                Annotated::new(ExtraValue(Value::String("foo@example.com".to_string()))),
+            );
+            map.insert(
+                "name".to_string(),
+                Annotated::new(ExtraValue(Value::String("foo".to_string()))),
+            );
+            Annotated::new(map)
+        },
+        ..Default::default()
+    });
+
+    let compiled = config.compiled();
+    let mut processor = PiiProcessor::new(&compiled);
+    process_value(&mut event, &mut processor, ProcessingState::root()).unwrap();
+
+    // neither the event nor the extra object are removed, since they have no value to match
+    let extra = event.value().unwrap().extra.value().unwrap();
+    assert_eq!(
+        extra.get("contact").unwrap().value(),
+        Some(&ExtraValue(Value::String("foo@example.com".to_string())))
+    );
+    assert!(extra.get("name").unwrap().value().is_none());
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::processor::{Path, SelectorSpec};
+
+/// A field that was scrubbed, along with the selector of the rule that applied to it.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize)]
+pub struct RedactedField {
+    /// The rendered path of the field, such as `extra.foo`.
+    pub path: String,
+    /// The selector that matched the field.
+    pub selector: SelectorSpec,
+}
+
+/// Collects the fields that were scrubbed during PII processing.
+///
+/// This is meant to report to users what was removed from their data.
+#[derive(Clone, Debug, Default)]
+pub struct RedactionSummary {
+    fields: BTreeSet<RedactedField>,
+}
+
+impl RedactionSummary {
+    /// Creates an empty summary.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the field at `path` was scrubbed by a rule applied via `selector`.
+    pub fn note(&mut self, path: &Path<'_>, selector: &SelectorSpec) {
+        self.fields.insert(RedactedField {
+            path: path.to_string(),
+            selector: selector.clone(),
+        });
+    }
+
+    /// Returns all recorded fields, sorted by path and without duplicates.
+    pub fn finish(self) -> Vec<RedactedField> {
+        self.fields.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processor::ProcessingState;
+
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let extra_state = ProcessingState::root().enter_static("extra", None, None);
+        let foo_state = extra_state.enter_static("foo", None, None);
+        let bar_state = extra_state.enter_static("bar", None, None);
+
+        let selector: SelectorSpec = "extra.*".parse().unwrap();
+        let mut summary = RedactionSummary::new();
+        summary.note(&foo_state.path(), &selector);
+        summary.note(&bar_state.path(), &selector);
+        summary.note(&foo_state.path(), &selector);
+
+        let fields = summary.finish();
+        let paths: Vec<_> = fields.iter().map(|field| field.path.as_str()).collect();
+        assert_eq!(paths, vec!["extra.bar", "extra.foo"]);
+        assert!(fields.iter().all(|field| field.selector == selector));
+    }
+}
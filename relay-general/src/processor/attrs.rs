@@ -1,9 +1,16 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt;
+use std::str::FromStr;
 
 use failure::Fail;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
+use unicode_segmentation::UnicodeSegmentation;
 
+use crate::processor::selector::key_matches;
 use crate::processor::{ProcessValue, SelectorPathItem, SelectorSpec};
 use crate::types::Annotated;
 
@@ -12,6 +19,11 @@ use crate::types::Annotated;
 #[fail(display = "unknown value type")]
 pub struct UnknownValueTypeError;
 
+/// Error for values that do not match the `match_regex` of their field.
+#[derive(Debug, Fail)]
+#[fail(display = "invalid characters in string")]
+pub struct RegexMismatch;
+
 /// The (simplified) type of a value.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum ValueType {
@@ -51,6 +63,116 @@ impl ValueType {
     pub fn for_field<T: ProcessValue>(field: &Annotated<T>) -> Option<Self> {
         field.value().and_then(ProcessValue::value_type)
     }
+
+    /// Returns the type of the field's value, or the given fallback if it is absent or untyped.
+    ///
+    /// Use this with the declared type of a field, so that selectors such as `$string` also match
+    /// fields without a value.
+    pub fn for_annotated_or<T: ProcessValue>(field: &Annotated<T>, fallback: ValueType) -> Self {
+        Self::for_field(field).unwrap_or(fallback)
+    }
+
+    /// Returns `true` if values of this type can have child values.
+    ///
+    /// Containers are `Array` and `Object`, the roots `Event` and `Attachments`, all structured
+    /// protocol interfaces (`Exception`, `Stacktrace`, `Frame`, `Request`, `User`, `LogEntry`,
+    /// `Thread`, `Breadcrumb`, `Span` and `ClientSdkInfo`) and `Minidump`, which contains memory
+    /// regions. Every value type is either a container or a scalar.
+    pub fn is_container(self) -> bool {
+        match self {
+            ValueType::Array
+            | ValueType::Object
+            | ValueType::Event
+            | ValueType::Attachments
+            | ValueType::Exception
+            | ValueType::Stacktrace
+            | ValueType::Frame
+            | ValueType::Request
+            | ValueType::User
+            | ValueType::LogEntry
+            | ValueType::Thread
+            | ValueType::Breadcrumb
+            | ValueType::Span
+            | ValueType::ClientSdkInfo
+            | ValueType::Minidump => true,
+
+            ValueType::String
+            | ValueType::Binary
+            | ValueType::Number
+            | ValueType::Boolean
+            | ValueType::DateTime
+            | ValueType::Message
+            | ValueType::HeapMemory
+            | ValueType::StackMemory => false,
+        }
+    }
+
+    /// Returns `true` if values of this type cannot have child values.
+    ///
+    /// Scalars are the basic types `String`, `Binary`, `Number`, `Boolean` and `DateTime`, as well
+    /// as `Message`, which is a string, and the binary memory regions `HeapMemory` and
+    /// `StackMemory`. This is the inverse of `is_container`.
+    pub fn is_scalar(self) -> bool {
+        !self.is_container()
+    }
+
+    /// Returns the type that usually encloses values of this type, if any.
+    ///
+    /// Intermediate arrays and objects are skipped, so breadcrumbs report `Event` even though they
+    /// are stored in a list. Values of basic types can appear anywhere and return `None`, as do the
+    /// roots `Event` and `Attachments`. Some types can have other parents too, for instance stack
+    /// traces also appear on threads.
+    pub fn typical_parent(self) -> Option<ValueType> {
+        Some(match self {
+            ValueType::String
+            | ValueType::Binary
+            | ValueType::Number
+            | ValueType::Boolean
+            | ValueType::DateTime
+            | ValueType::Array
+            | ValueType::Object
+            | ValueType::Event
+            | ValueType::Attachments => return None,
+
+            ValueType::Exception
+            | ValueType::Request
+            | ValueType::User
+            | ValueType::LogEntry
+            | ValueType::Thread
+            | ValueType::Breadcrumb
+            | ValueType::Span
+            | ValueType::ClientSdkInfo => ValueType::Event,
+
+            ValueType::Stacktrace => ValueType::Exception,
+            ValueType::Frame => ValueType::Stacktrace,
+            ValueType::Message => ValueType::LogEntry,
+            ValueType::Minidump => ValueType::Attachments,
+            ValueType::HeapMemory | ValueType::StackMemory => ValueType::Minidump,
+        })
+    }
+
+    /// Returns the value types that commonly hold PII, see `is_pii_relevant`.
+    pub fn pii_relevant_types() -> &'static [ValueType] {
+        &[
+            // free-form text can contain anything
+            ValueType::String,
+            // identifies the user by id, name, email or ip address
+            ValueType::User,
+            // carries cookies, headers, query strings and form data
+            ValueType::Request,
+            // log messages and their parameters are written by users
+            ValueType::LogEntry,
+            ValueType::Message,
+        ]
+    }
+
+    /// Returns `true` if values of this type commonly hold PII.
+    ///
+    /// Rule generators can use this to target the types that users most often want to scrub.
+    /// Other types, like stack frames, may still contain PII in their string fields.
+    pub fn is_pii_relevant(self) -> bool {
+        Self::pii_relevant_types().contains(&self)
+    }
 }
 
 derive_fromstr_and_display!(ValueType, UnknownValueTypeError, {
@@ -79,6 +201,21 @@ derive_fromstr_and_display!(ValueType, UnknownValueTypeError, {
     ValueType::StackMemory => "stack_memory",
 });
 
+/// Error for invalid `MaxChars` strings.
+#[derive(Debug, Fail)]
+pub enum ParseMaxCharsError {
+    /// The string is neither a known name nor a parameterized limit.
+    #[fail(
+        display = "unknown max chars `{}`, expected a name or `hard:N` or `soft:N`",
+        _0
+    )]
+    UnknownName(String),
+
+    /// The length of a parameterized limit is not a valid number.
+    #[fail(display = "invalid max chars length `{}`", _0)]
+    InvalidLength(String),
+}
+
 /// The maximum length of a field.
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
 pub enum MaxChars {
@@ -100,6 +237,50 @@ pub enum MaxChars {
 }
 
 impl MaxChars {
+    /// Resolves a named limit, such as `"logger"`.
+    ///
+    /// The names are the same as in the `max_chars` field attribute. For multi-word names, the
+    /// spelling without underscores (`"shortpath"`) is accepted as well. Parameterized limits
+    /// (`Hard` and `Soft`) cannot be resolved by name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "hash" => MaxChars::Hash,
+            "enumlike" => MaxChars::EnumLike,
+            "summary" => MaxChars::Summary,
+            "message" => MaxChars::Message,
+            "symbol" => MaxChars::Symbol,
+            "path" => MaxChars::Path,
+            "short_path" | "shortpath" => MaxChars::ShortPath,
+            "logger" => MaxChars::Logger,
+            "email" => MaxChars::Email,
+            "culprit" => MaxChars::Culprit,
+            "tag_key" | "tagkey" => MaxChars::TagKey,
+            "tag_value" | "tagvalue" => MaxChars::TagValue,
+            "environment" => MaxChars::Environment,
+            _ => return None,
+        })
+    }
+
+    /// Returns the canonical name of a named limit, or `None` for `Hard` and `Soft` limits.
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self {
+            MaxChars::Hash => "hash",
+            MaxChars::EnumLike => "enumlike",
+            MaxChars::Summary => "summary",
+            MaxChars::Message => "message",
+            MaxChars::Symbol => "symbol",
+            MaxChars::Path => "path",
+            MaxChars::ShortPath => "short_path",
+            MaxChars::Logger => "logger",
+            MaxChars::Email => "email",
+            MaxChars::Culprit => "culprit",
+            MaxChars::TagKey => "tag_key",
+            MaxChars::TagValue => "tag_value",
+            MaxChars::Environment => "environment",
+            MaxChars::Hard(_) | MaxChars::Soft(_) => return None,
+        })
+    }
+
     /// The cap in number of unicode characters.
     pub fn limit(self) -> usize {
         match self {
@@ -141,8 +322,103 @@ impl MaxChars {
             MaxChars::Hard(_) => 0,
         }
     }
+
+    /// Truncates a string that exceeds this limit.
+    ///
+    /// Strings of up to `limit() + allowance()` characters are returned unchanged. Longer strings
+    /// are cut down to `limit()` characters, the last three of which are replaced with an ellipsis
+    /// marker (`...`). Lengths are counted in unicode characters, not bytes.
+    pub fn truncate(self, value: &str) -> Cow<'_, str> {
+        self.truncate_with(value, "...")
+    }
+
+    /// Truncates a string that exceeds this limit, ending it with a custom marker.
+    ///
+    /// This works like `truncate`, but appends `marker` instead of `...`. The marker counts
+    /// towards the limit, so the result never has more than `limit()` characters. A marker that
+    /// is longer than the limit is cut off itself.
+    pub fn truncate_with<'a>(self, value: &'a str, marker: &str) -> Cow<'a, str> {
+        let hard_limit = self.limit() + self.allowance();
+        if bytecount::num_chars(value.as_bytes()) <= hard_limit {
+            return Cow::Borrowed(value);
+        }
+
+        let marker_chars = bytecount::num_chars(marker.as_bytes());
+        let marker = &marker[..char_boundary(marker, self.limit())];
+        let end = char_boundary(value, self.limit().saturating_sub(marker_chars));
+
+        Cow::Owned(format!("{}{}", &value[..end], marker))
+    }
+
+    /// Truncates a string that exceeds this limit, counting grapheme clusters.
+    ///
+    /// `truncate` counts unicode scalar values, so it can cut a user-perceived character apart,
+    /// such as an emoji with modifiers. This counts extended grapheme clusters instead and only
+    /// cuts between them. Otherwise, it behaves like `truncate`.
+    pub fn truncate_graphemes(self, value: &str) -> Cow<'_, str> {
+        let hard_limit = self.limit() + self.allowance();
+        if value.graphemes(true).nth(hard_limit).is_none() {
+            return Cow::Borrowed(value);
+        }
+
+        let end = value
+            .grapheme_indices(true)
+            .nth(self.limit().saturating_sub(3))
+            .map_or(value.len(), |(index, _)| index);
+
+        Cow::Owned(format!("{}...", &value[..end]))
+    }
+}
+
+/// Returns the byte offset after the first `chars` characters of `value`.
+fn char_boundary(value: &str, chars: usize) -> usize {
+    value
+        .char_indices()
+        .nth(chars)
+        .map_or(value.len(), |(index, _)| index)
+}
+
+impl FromStr for MaxChars {
+    type Err = ParseMaxCharsError;
+
+    /// Parses a named limit (see `from_name`), or a parameterized limit like `hard:200`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(max_chars) = MaxChars::from_name(s) {
+            return Ok(max_chars);
+        }
+
+        let mut parts = s.splitn(2, ':');
+        let constructor: fn(usize) -> MaxChars = match parts.next() {
+            Some("hard") => MaxChars::Hard,
+            Some("soft") => MaxChars::Soft,
+            _ => return Err(ParseMaxCharsError::UnknownName(s.to_owned())),
+        };
+
+        let length = parts.next().unwrap_or_default();
+        match length.parse() {
+            Ok(length) => Ok(constructor(length)),
+            Err(_) => Err(ParseMaxCharsError::InvalidLength(length.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for MaxChars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MaxChars::Hard(len) => write!(f, "hard:{}", len),
+            MaxChars::Soft(len) => write!(f, "soft:{}", len),
+            named => f.write_str(named.name().unwrap_or_default()),
+        }
+    }
 }
 
+impl_str_serde!(MaxChars);
+
+/// Error for unknown bag sizes.
+#[derive(Debug, Fail)]
+#[fail(display = "unknown bag size, expected one of small, medium, large, larger or massive")]
+pub struct UnknownBagSizeError;
+
 /// The maximum size of a databag.
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
 pub enum BagSize {
@@ -177,6 +453,16 @@ impl BagSize {
     }
 }
 
+derive_fromstr_and_display!(BagSize, UnknownBagSizeError, {
+    BagSize::Small => "small",
+    BagSize::Medium => "medium",
+    BagSize::Large => "large",
+    BagSize::Larger => "larger",
+    BagSize::Massive => "massive",
+});
+
+impl_str_serde!(BagSize);
+
 /// Whether an attribute should be PII-strippable/should be subject to datascrubbers
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Pii {
@@ -189,6 +475,33 @@ pub enum Pii {
     Maybe,
 }
 
+impl Pii {
+    /// Returns whether a field with this attribute is scrubbed by a rule that addresses it.
+    ///
+    /// `rule_is_specific` indicates whether the rule's selector is specific, see
+    /// `SelectorSpec::is_specific`.
+    pub fn effective(self, rule_is_specific: bool) -> bool {
+        match self {
+            Pii::True => true,
+            Pii::False => false,
+            Pii::Maybe => rule_is_specific,
+        }
+    }
+}
+
+/// Error for unknown pii values.
+#[derive(Debug, Fail)]
+#[fail(display = "unknown pii value, expected one of true, false or maybe")]
+pub struct UnknownPiiError;
+
+derive_fromstr_and_display!(Pii, UnknownPiiError, {
+    Pii::True => "true",
+    Pii::False => "false",
+    Pii::Maybe => "maybe",
+});
+
+impl_str_serde!(Pii);
+
 /// Meta information about a field.
 #[derive(Debug, Clone, Copy)]
 pub struct FieldAttrs {
@@ -228,6 +541,17 @@ impl FieldAttrs {
         }
     }
 
+    /// Returns the default attributes for values of the given type.
+    ///
+    /// Fields declare their own attributes, which take precedence over these. Types without
+    /// specific defaults receive the same attributes as `FieldAttrs::new`.
+    pub fn defaults_for(value_type: ValueType) -> Self {
+        match value_type {
+            ValueType::Message => FieldAttrs::new().max_chars(MaxChars::Message),
+            _ => DEFAULT_FIELD_ATTRS,
+        }
+    }
+
     /// Sets whether a value in this field is required.
     pub const fn required(mut self, required: bool) -> Self {
         self.required = required;
@@ -274,6 +598,252 @@ impl FieldAttrs {
         self.retain = retain;
         self
     }
+
+    /// Returns the additional property keys that should be dropped during normalization.
+    ///
+    /// This is all of the given keys, unless `retain` is set.
+    pub fn filter_additional<'k, I>(&self, keys: I) -> Vec<&'k str>
+    where
+        I: Iterator<Item = &'k str>,
+    {
+        if self.retain {
+            Vec::new()
+        } else {
+            keys.collect()
+        }
+    }
+
+    /// Returns `true` if the value matches `match_regex`, or if there is no regex.
+    ///
+    /// Regexes are compiled once and must be declared in `declare_used_field_regexes`. This panics
+    /// for undeclared regexes.
+    pub fn matches_value(&self, value: &str) -> bool {
+        match self.match_regex {
+            Some(regex) => get_regex(regex).is_match(value),
+            None => true,
+        }
+    }
+
+    /// Validates a value against `match_regex`, see `matches_value`.
+    pub fn validate_value(&self, value: &str) -> Result<(), RegexMismatch> {
+        if self.matches_value(value) {
+            Ok(())
+        } else {
+            Err(RegexMismatch)
+        }
+    }
+
+    /// Checks a value against the `required` and `nonempty` flags.
+    ///
+    /// `is_empty` is only considered if the value is present. See `nonempty` for the difference
+    /// between missing and empty values.
+    pub fn check_presence(&self, is_present: bool, is_empty: bool) -> PresenceResult {
+        if !is_present {
+            if self.required {
+                PresenceResult::MissingRequired
+            } else {
+                PresenceResult::Ok
+            }
+        } else if is_empty && self.nonempty {
+            PresenceResult::EmptyNotAllowed
+        } else {
+            PresenceResult::Ok
+        }
+    }
+
+    /// Returns a copy of these attributes with all fields set in the patch overridden.
+    pub fn with_overrides(&self, overrides: FieldAttrsPatch) -> Self {
+        let mut attrs = *self;
+
+        if let Some(name) = overrides.name {
+            attrs.name = Some(name);
+        }
+        if let Some(required) = overrides.required {
+            attrs.required = required;
+        }
+        if let Some(nonempty) = overrides.nonempty {
+            attrs.nonempty = nonempty;
+        }
+        if let Some(trim_whitespace) = overrides.trim_whitespace {
+            attrs.trim_whitespace = trim_whitespace;
+        }
+        if let Some(match_regex) = overrides.match_regex {
+            attrs.match_regex = Some(match_regex);
+        }
+        if let Some(max_chars) = overrides.max_chars {
+            attrs.max_chars = Some(max_chars);
+        }
+        if let Some(bag_size) = overrides.bag_size {
+            attrs.bag_size = Some(bag_size);
+        }
+        if let Some(pii) = overrides.pii {
+            attrs.pii = pii;
+        }
+        if let Some(retain) = overrides.retain {
+            attrs.retain = retain;
+        }
+
+        attrs
+    }
+}
+
+/// The outcome of `FieldAttrs::check_presence`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PresenceResult {
+    /// The value satisfies the field's presence requirements.
+    Ok,
+    /// The field is required, but the value is missing.
+    MissingRequired,
+    /// The field must not be empty, but the value is empty.
+    EmptyNotAllowed,
+}
+
+/// A partial set of `FieldAttrs` to layer on top of existing attributes.
+///
+/// Every field that is `Some` overrides the respective field in `FieldAttrs`, all other fields are
+/// left untouched. See `FieldAttrs::with_overrides`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FieldAttrsPatch {
+    /// Overrides the name of the field.
+    pub name: Option<&'static str>,
+    /// Overrides if the field is required.
+    pub required: Option<bool>,
+    /// Overrides if the field should be non-empty.
+    pub nonempty: Option<bool>,
+    /// Overrides whether to trim whitespace from this string.
+    pub trim_whitespace: Option<bool>,
+    /// Overrides the regex to validate the (string) value against.
+    pub match_regex: Option<&'static str>,
+    /// Overrides the maximum char length of this field.
+    pub max_chars: Option<MaxChars>,
+    /// Overrides the maximum bag size of this field.
+    pub bag_size: Option<BagSize>,
+    /// Overrides the type of PII on the field.
+    pub pii: Option<Pii>,
+    /// Overrides whether additional properties should be retained during normalization.
+    pub retain: Option<bool>,
+}
+
+macro_rules! declare_used_field_regexes {
+    ($($ident:ident: $regex:expr),* $(,)*) => {
+        fn get_regex(name: &'static str) -> &'static Regex {
+            lazy_static::lazy_static! {
+                $(
+                    static ref $ident: Regex = Regex::new($regex).unwrap();
+                )*
+            };
+
+            match name {
+                $($regex => &$ident, )*
+                _ => panic!("Please declare Regex {} using declare_used_field_regexes.", name),
+            }
+        }
+
+        fn find_declared_regex(pattern: &str) -> Option<&'static str> {
+            match pattern {
+                $($regex => Some($regex), )*
+                _ => None,
+            }
+        }
+    }
+}
+
+// Pre-built list of regexes for max performance. The identifier in front is arbitrary, but needs
+// to be unique.
+declare_used_field_regexes![
+    A: r"^[^\r\n\f\t/]*\z",
+    B: r"^[^\r\n\x0C/]+$",
+    C: r"^[^\r\n]*\z",
+    D: r"^[a-zA-Z0-9_\.:-]+\z",
+    E: r"^\s*[a-zA-Z0-9_.-]*\s*$",
+    F: r"^[^\n]+\z",
+];
+
+/// Error for regexes that are not declared in `declare_used_field_regexes`.
+#[derive(Debug, Fail)]
+#[fail(display = "undeclared field regex: {}", _0)]
+pub struct UndeclaredRegexError(pub String);
+
+/// An owned and serializable form of `FieldAttrs`.
+///
+/// This allows tools to store field attributes, for instance as JSON. Limits and PII attributes
+/// are written in their string forms, such as `"hard:200"` or `"maybe"`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FieldAttrsConfig {
+    /// Optionally the name of the field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// If the field is required.
+    pub required: bool,
+    /// If the field should be non-empty.
+    pub nonempty: bool,
+    /// Whether to trim whitespace from this string.
+    pub trim_whitespace: bool,
+    /// A regex to validate the (string) value against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_regex: Option<String>,
+    /// The maximum char length of this field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_chars: Option<MaxChars>,
+    /// The maximum bag size of this field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bag_size: Option<BagSize>,
+    /// The type of PII on the field.
+    pub pii: Pii,
+    /// Whether additional properties should be retained during normalization.
+    pub retain: bool,
+}
+
+impl FieldAttrsConfig {
+    /// Converts back into `FieldAttrs`.
+    ///
+    /// Field attributes can only refer to regexes declared in `declare_used_field_regexes`, so
+    /// other regexes are rejected. The name is not restored, since it must be a static string. It
+    /// is not used during processing.
+    pub fn to_field_attrs(&self) -> Result<FieldAttrs, UndeclaredRegexError> {
+        let match_regex = match self.match_regex {
+            Some(ref pattern) => match find_declared_regex(pattern) {
+                Some(pattern) => Some(pattern),
+                None => return Err(UndeclaredRegexError(pattern.clone())),
+            },
+            None => None,
+        };
+
+        Ok(FieldAttrs {
+            name: None,
+            required: self.required,
+            nonempty: self.nonempty,
+            trim_whitespace: self.trim_whitespace,
+            match_regex,
+            max_chars: self.max_chars,
+            bag_size: self.bag_size,
+            pii: self.pii,
+            retain: self.retain,
+        })
+    }
+}
+
+impl From<&FieldAttrs> for FieldAttrsConfig {
+    fn from(attrs: &FieldAttrs) -> Self {
+        FieldAttrsConfig {
+            name: attrs.name.map(str::to_owned),
+            required: attrs.required,
+            nonempty: attrs.nonempty,
+            trim_whitespace: attrs.trim_whitespace,
+            match_regex: attrs.match_regex.map(str::to_owned),
+            max_chars: attrs.max_chars,
+            bag_size: attrs.bag_size,
+            pii: attrs.pii,
+            retain: attrs.retain,
+        }
+    }
+}
+
+impl Default for FieldAttrsConfig {
+    fn default() -> Self {
+        FieldAttrsConfig::from(&FieldAttrs::new())
+    }
 }
 
 static DEFAULT_FIELD_ATTRS: FieldAttrs = FieldAttrs::new();
@@ -286,8 +856,18 @@ impl Default for FieldAttrs {
     }
 }
 
+/// An owned segment of a path, such as a key or an array index.
+///
+/// Unlike the path of a `ProcessingState`, a list of these can be stored and evaluated later. See
+/// `SelectorSpec::matches_path`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
 #[derive(Debug, Clone, Eq, Ord, PartialOrd)]
-enum PathItem<'a> {
+pub(super) enum PathItem<'a> {
     StaticKey(&'a str),
     Index(usize),
 }
@@ -368,6 +948,85 @@ impl<'a> ProcessingState<'a> {
         }
     }
 
+    /// Builds the processing state for a dotted path and calls `f` with the innermost state.
+    ///
+    /// Segments consisting only of digits are entered as indices, all other segments as keys. None
+    /// of the states carry field attributes or value types. Since every state borrows its parent,
+    /// the chain only lives for the duration of the callback.
+    pub fn with_path_str<F, R>(path: &str, f: F) -> R
+    where
+        F: FnOnce(&ProcessingState<'_>) -> R,
+    {
+        let segments: Vec<PathSegment> = if path.is_empty() {
+            Vec::new()
+        } else {
+            path.split('.')
+                .map(|segment| {
+                    let is_index =
+                        !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+                    match segment.parse() {
+                        Ok(index) if is_index => PathSegment::Index(index),
+                        _ => PathSegment::Key(segment.to_owned()),
+                    }
+                })
+                .collect()
+        };
+
+        let value_types = vec![None; segments.len()];
+        Self::with_path(&segments, None, &value_types, f)
+    }
+
+    /// Builds the state for the given path and passes it to `f`.
+    ///
+    /// `segments` addresses the value from the root. `root_type` is the value type of the root,
+    /// and `value_types[i]` is the value type of the value at `segments[i]`. The states only live
+    /// on the stack, which is why they are handed to a callback.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `segments` and `value_types` have different lengths.
+    pub fn with_path<F, R>(
+        segments: &[PathSegment],
+        root_type: Option<ValueType>,
+        value_types: &[Option<ValueType>],
+        f: F,
+    ) -> R
+    where
+        F: FnOnce(&ProcessingState<'_>) -> R,
+    {
+        fn enter<F, R>(
+            state: &ProcessingState<'_>,
+            segments: &[PathSegment],
+            value_types: &[Option<ValueType>],
+            f: F,
+        ) -> R
+        where
+            F: FnOnce(&ProcessingState<'_>) -> R,
+        {
+            let ((segment, segments), (&value_type, value_types)) =
+                match (segments.split_first(), value_types.split_first()) {
+                    (Some(segment), Some(value_type)) => (segment, value_type),
+                    _ => return f(state),
+                };
+
+            let inner_state = match *segment {
+                PathSegment::Key(ref key) => state.enter_borrowed(key, None, value_type),
+                PathSegment::Index(index) => state.enter_index(index, None, value_type),
+            };
+
+            enter(&inner_state, segments, value_types, f)
+        }
+
+        assert_eq!(
+            segments.len(),
+            value_types.len(),
+            "every segment needs a value type"
+        );
+
+        let root = ProcessingState::new_root(None, root_type);
+        enter(&root, segments, value_types, f)
+    }
+
     /// Derives a processing state by entering a static key.
     pub fn enter_static(
         &'a self,
@@ -452,6 +1111,27 @@ impl<'a> ProcessingState<'a> {
         }
     }
 
+    /// Returns the pii attribute that children of this state inherit, see `inner_attrs`.
+    ///
+    /// Children of fields with `Pii::False` fall back to the default attrs, which are not pii.
+    pub fn child_pii(&self) -> Pii {
+        self.inner_attrs().map_or(Pii::False, |attrs| attrs.pii)
+    }
+
+    /// Returns the index of this state if it was entered with `enter_index`.
+    ///
+    /// Unlike `Path::index`, this does not look through newtype wrappers.
+    pub fn current_index(&self) -> Option<usize> {
+        self.path_item.as_ref()?.index()
+    }
+
+    /// Returns the state this state was entered from, or `None` for the root.
+    ///
+    /// Note that the parent of a newtype wrapper has the same path and depth.
+    pub fn parent(&self) -> Option<&'a ProcessingState<'a>> {
+        self.parent
+    }
+
     /// Iterates through this state and all its ancestors up the hierarchy.
     pub fn iter(&'a self) -> ProcessingStateIter<'a> {
         ProcessingStateIter {
@@ -460,6 +1140,36 @@ impl<'a> ProcessingState<'a> {
         }
     }
 
+    /// Returns whether any ancestor of this state has the given value type.
+    ///
+    /// The state itself is not considered, including newtype wrappers at the same depth. This is a
+    /// cheap alternative to matching a `$type.**` selector.
+    pub fn is_inside(&'a self, value_type: ValueType) -> bool {
+        self.iter()
+            .filter(|state| state.depth < self.depth)
+            .any(|state| state.value_type == Some(value_type))
+    }
+
+    /// Renders the path from the root to this state along with value types, for debugging.
+    ///
+    /// Segments are separated by `>` and followed by their value type in parentheses, if any. The
+    /// root is rendered as its value type, for example: `event > user(user) > 0`.
+    pub fn describe(&'a self) -> String {
+        let mut segments = Vec::with_capacity(self.depth + 1);
+        for state in self.iter().filter(|state| state.entered_anything()) {
+            let segment = match (&state.path_item, state.value_type) {
+                (Some(item), Some(ty)) => format!("{}({})", item, ty),
+                (Some(item), None) => item.to_string(),
+                (None, Some(ty)) => ty.to_string(),
+                (None, None) => "root".to_owned(),
+            };
+            segments.push(segment);
+        }
+
+        segments.reverse();
+        segments.join(" > ")
+    }
+
     /// Return the depth (~ indentation level) of the currently processed value.
     pub fn depth(&'a self) -> usize {
         self.depth
@@ -516,6 +1226,47 @@ impl<'a> Default for ProcessingState<'a> {
     }
 }
 
+/// A single comparison of a selector item with a state, see `Path::matches_selector_traced`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MatchStep {
+    /// The selector item that was compared.
+    pub item: SelectorPathItem,
+    /// The rendered path of the state it was compared with.
+    pub path: String,
+    /// Whether the item matched the state.
+    pub matched: bool,
+}
+
+/// Options for evaluating selectors in `Path::matches_selector_impl`.
+#[derive(Clone, Copy, Default)]
+struct MatchContext<'t> {
+    /// Treat states entered by array indices as transparent.
+    skip_indices: bool,
+    /// Records all comparisons, if set.
+    trace: Option<&'t RefCell<Vec<MatchStep>>>,
+    /// The value at the path, if known.
+    value: Option<&'t str>,
+    /// Whether the selector is evaluated below an odd number of negations.
+    ///
+    /// Without a value, value predicates are not applicable. They then match only when negated,
+    /// so that neither a predicate nor its negation matches.
+    negated: bool,
+}
+
+impl MatchContext<'_> {
+    fn matches_state(&self, item: &SelectorPathItem, state: &ProcessingState<'_>) -> bool {
+        let matched = item.matches_state(state);
+        if let Some(trace) = self.trace {
+            trace.borrow_mut().push(MatchStep {
+                item: item.clone(),
+                path: state.path().to_string(),
+                matched,
+            });
+        }
+        matched
+    }
+}
+
 /// Represents the path in a structure
 #[derive(Debug)]
 pub struct Path<'a>(&'a ProcessingState<'a>);
@@ -533,75 +1284,309 @@ impl<'a> Path<'a> {
         PathItem::index(self.0.path_item()?)
     }
 
+    /// Returns the path items from the root down to the current state.
+    pub(super) fn items(&self) -> Vec<&'a PathItem<'a>> {
+        let mut items = Vec::with_capacity(self.0.depth);
+        for state in self.0.iter() {
+            if let Some(ref path_item) = state.path_item {
+                items.push(path_item)
+            }
+        }
+
+        items.reverse();
+        items
+    }
+
+    /// Checks whether this path begins with the given segments.
+    ///
+    /// Keys are compared exactly. A path starts with itself and with the empty prefix.
+    pub fn starts_with(&self, prefix: &[PathSegment]) -> bool {
+        let items = self.items();
+        if prefix.len() > items.len() {
+            return false;
+        }
+
+        items
+            .iter()
+            .zip(prefix)
+            .all(|(item, segment)| match (*item, segment) {
+                (PathItem::StaticKey(key), PathSegment::Key(ref prefix_key)) => key == prefix_key,
+                (PathItem::Index(index), PathSegment::Index(prefix_index)) => index == prefix_index,
+                _ => false,
+            })
+    }
+
+    /// Renders the path with array indices in brackets, such as `user.extra.foo[0]`.
+    ///
+    /// Unlike `Display`, this distinguishes indices from keys that consist of digits.
+    pub fn to_bracket_notation(&self) -> String {
+        let mut rv = String::new();
+        for item in self.items() {
+            match *item {
+                PathItem::StaticKey(key) => {
+                    if !rv.is_empty() {
+                        rv.push('.');
+                    }
+                    rv.push_str(key);
+                }
+                PathItem::Index(index) => {
+                    rv.push('[');
+                    rv.push_str(&index.to_string());
+                    rv.push(']');
+                }
+            }
+        }
+        rv
+    }
+
     /// Checks if a path matches given selector.
     pub fn matches_selector(&self, selector: &SelectorSpec) -> bool {
+        self.matches_selector_at(selector).is_some()
+    }
+
+    /// Checks if a path matches given selector and returns the depth at which the match anchored.
+    ///
+    /// For selectors with a deep wildcard, this is the depth of the state matched by the last
+    /// selector item before the `**`, which is the root of the matched subtree. All other matches
+    /// anchor at the current state and return its depth. For conjunctions, the deepest anchor is
+    /// returned; for disjunctions, the anchor of the first matching branch.
+    ///
+    /// Returns `None` if and only if `matches_selector` returns `false`.
+    pub fn matches_selector_at(&self, selector: &SelectorSpec) -> Option<usize> {
+        self.matches_selector_impl(selector, MatchContext::default())
+    }
+
+    /// Checks if a field matches the given selector, taking its value into account.
+    ///
+    /// Unlike `matches_selector`, this can evaluate `SelectorSpec::ValueMatches`. Just like in
+    /// the PII processor, fields with `Pii::False` never match, and fields with `Pii::Maybe` only
+    /// match specific selectors.
+    pub fn matches_value_selector(&self, pii: Pii, selector: &SelectorSpec, value: &str) -> bool {
+        if !pii.effective(selector.is_specific()) {
+            return false;
+        }
+
+        let context = MatchContext {
+            value: Some(value),
+            ..MatchContext::default()
+        };
+
+        self.matches_selector_impl(selector, context).is_some()
+    }
+
+    /// Checks if a path matches given selector and records every comparison on the way.
+    ///
+    /// This is meant for debugging selectors and returns the same result as `matches_selector`.
+    /// The steps are listed in the order they were evaluated, which is from the leaf upwards.
+    pub fn matches_selector_traced(&self, selector: &SelectorSpec) -> (bool, Vec<MatchStep>) {
+        let trace = RefCell::new(Vec::new());
+        let context = MatchContext {
+            trace: Some(&trace),
+            ..MatchContext::default()
+        };
+
+        let matches = self.matches_selector_impl(selector, context).is_some();
+        (matches, trace.into_inner())
+    }
+
+    fn matches_literal_path(&self, path: &[SelectorPathItem]) -> Option<usize> {
+        let states = self.0.iter().filter(|state| state.entered_anything());
+        for (item, state) in path.iter().rev().zip(states) {
+            let matches = match (item, state.path_item()) {
+                (SelectorPathItem::Key(ref key), Some(PathItem::StaticKey(k))) => {
+                    key_matches(k, key)
+                }
+                (SelectorPathItem::Index(idx), Some(PathItem::Index(i))) => idx == i,
+                _ => false,
+            };
+
+            if !matches {
+                return None;
+            }
+        }
+
+        Some(self.0.depth)
+    }
+
+    fn matches_selector_impl(
+        &self,
+        selector: &SelectorSpec,
+        context: MatchContext<'_>,
+    ) -> Option<usize> {
         match *selector {
             SelectorSpec::Path(ref path) => {
-                // fastest path: the selector is deeper than the current structure.
-                if path.len() > self.0.depth {
-                    return false;
+                // fastest path: the selector is deeper than the current structure. A root anchor
+                // matches the root state, which does not count towards the depth.
+                let anchored = path.first() == Some(&SelectorPathItem::Root);
+                if path.len() - anchored as usize > self.0.depth {
+                    return None;
+                }
+
+                // fast path for selectors made of keys and indices only: compare segments directly
+                if context.trace.is_none()
+                    && !context.skip_indices
+                    && path.iter().all(SelectorPathItem::is_literal)
+                {
+                    return self.matches_literal_path(path);
                 }
 
                 // fast path: we do not have any deep matches
-                let mut state_iter = self.0.iter().filter(|state| state.entered_anything());
+                let mut state_iter = self.0.iter().filter(|state| {
+                    state.entered_anything()
+                        && !(context.skip_indices && state.path().index().is_some())
+                });
                 let mut selector_iter = path.iter().rev();
-                let mut depth_match = false;
+                let mut deep_wildcard = None;
                 for state in &mut state_iter {
                     if !match selector_iter.next() {
-                        Some(SelectorPathItem::DeepWildcard) => {
-                            depth_match = true;
+                        Some(path_item) if path_item.is_deep_wildcard() => {
+                            deep_wildcard = Some(path_item);
                             break;
                         }
-                        Some(ref path_item) => path_item.matches_state(state),
+                        Some(ref path_item) => context.matches_state(path_item, state),
                         None => break,
                     } {
-                        return false;
+                        return None;
                     }
                 }
 
-                if !depth_match {
-                    return true;
-                }
+                let deep_wildcard = match deep_wildcard {
+                    Some(deep_wildcard) => deep_wildcard,
+                    None => return Some(self.0.depth),
+                };
 
                 // slow path: we collect the remaining states and skip up to the first
                 // match of the selector.
                 let remaining_states = state_iter.collect::<SmallVec<[&ProcessingState<'_>; 16]>>();
-                let mut selector_iter = selector_iter.rev().peekable();
+                if let SelectorPathItem::DeepWildcardBounded(max_levels) = *deep_wildcard {
+                    let prefix = &path[..selector_iter.len()];
+                    return matches_bounded_prefix(&remaining_states, prefix, max_levels, context);
+                }
+
+                let mut selector_iter = selector_iter.rev();
                 let first_selector_path = match selector_iter.next() {
                     Some(selector_path) => selector_path,
-                    None => return !remaining_states.is_empty(),
+                    None => return remaining_states.last().map(|state| state.depth),
                 };
                 let mut path_match_iterator = remaining_states
                     .iter()
                     .rev()
-                    .skip_while(|state| !first_selector_path.matches_state(state));
-                if path_match_iterator.next().is_none() {
-                    return false;
-                }
+                    .skip_while(|state| !context.matches_state(first_selector_path, state));
+                let mut anchor = path_match_iterator.next()?;
 
                 // then we check all remaining items and that nothing is left of the selector
-                path_match_iterator
-                    .zip(&mut selector_iter)
-                    .all(|(state, selector_path)| selector_path.matches_state(state))
-                    && selector_iter.next().is_none()
+                for selector_path in selector_iter {
+                    let state = path_match_iterator.next()?;
+                    if !context.matches_state(selector_path, state) {
+                        return None;
+                    }
+                    anchor = state;
+                }
+
+                Some(anchor.depth)
+            }
+            SelectorSpec::And(ref xs) => {
+                // an empty conjunction matches and anchors at the current state
+                let mut anchor = None;
+                for x in xs {
+                    let depth = self.matches_selector_impl(x, context)?;
+                    anchor = Some(anchor.map_or(depth, |a: usize| a.max(depth)));
+                }
+                Some(anchor.unwrap_or(self.0.depth))
+            }
+            SelectorSpec::Or(ref xs) => xs
+                .iter()
+                .find_map(|x| self.matches_selector_impl(x, context)),
+            SelectorSpec::Not(ref x) => {
+                let context = MatchContext {
+                    negated: !context.negated,
+                    ..context
+                };
+                match self.matches_selector_impl(x, context) {
+                    Some(_) => None,
+                    None => Some(self.0.depth),
+                }
+            }
+            SelectorSpec::IndexInsensitive(ref x) => {
+                let context = MatchContext {
+                    skip_indices: true,
+                    ..context
+                };
+                self.matches_selector_impl(x, context)
+            }
+            SelectorSpec::ValueMatches(ref predicate) => {
+                let matches = match context.value {
+                    Some(value) => predicate.is_match(value),
+                    None => context.negated,
+                };
+                if matches {
+                    Some(self.0.depth)
+                } else {
+                    None
+                }
             }
-            SelectorSpec::And(ref xs) => xs.iter().all(|x| self.matches_selector(x)),
-            SelectorSpec::Or(ref xs) => xs.iter().any(|x| self.matches_selector(x)),
-            SelectorSpec::Not(ref x) => !self.matches_selector(x),
         }
     }
 }
 
+/// Matches the part of a selector path before a bounded deep wildcard.
+///
+/// `states` are the states above the first level covered by the wildcard, starting from the
+/// closest one. The prefix must match consecutive states such that the wildcard covers at most
+/// `max_levels` levels in total. Without a prefix, the wildcard extends up to the root.
+fn matches_bounded_prefix(
+    states: &[&ProcessingState<'_>],
+    prefix: &[SelectorPathItem],
+    max_levels: usize,
+    context: MatchContext<'_>,
+) -> Option<usize> {
+    if prefix.is_empty() {
+        return match states.last() {
+            Some(root) if states.len() <= max_levels => Some(root.depth),
+            _ => None,
+        };
+    }
+
+    // the wildcard has already covered one level, so the prefix can start at most
+    // `max_levels - 1` states further up
+    (0..max_levels)
+        .filter(|skip| skip + prefix.len() <= states.len())
+        .find(|&skip| {
+            let candidates = &states[skip..skip + prefix.len()];
+            prefix
+                .iter()
+                .rev()
+                .zip(candidates)
+                .all(|(item, state)| context.matches_state(item, state))
+        })
+        .map(|skip| states[skip].depth)
+}
+
+/// Paths compare segment by segment from the root. Keys are ordered lexically and indices
+/// numerically, with keys before indices. A path sorts before all paths that it is a prefix of.
+impl PartialEq for Path<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Path<'_> {}
+
+impl PartialOrd for Path<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Path<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.items().cmp(&other.items())
+    }
+}
+
 impl<'a> fmt::Display for Path<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut items = Vec::with_capacity(self.0.depth);
-        for state in self.0.iter() {
-            if let Some(ref path_item) = state.path_item {
-                items.push(path_item)
-            }
-        }
-
-        for (idx, item) in items.into_iter().rev().enumerate() {
+        for (idx, item) in self.items().into_iter().enumerate() {
             if idx > 0 {
                 write!(f, ".")?;
             }
@@ -681,6 +1666,24 @@ fn test_path_matching() {
         .path()
         .matches_selector(&"**.$array".parse().unwrap()));
 
+    // any typed node, but not untyped ones
+    assert!(foo_state.path().matches_selector(&"$*".parse().unwrap()));
+    assert!(!zero_state.path().matches_selector(&"$*".parse().unwrap()));
+    assert!(zero_state
+        .path()
+        .matches_selector(&"$user.$*.foo.0".parse().unwrap()));
+    assert!(!zero_state
+        .path()
+        .matches_selector(&"$user.extra.foo.$*".parse().unwrap()));
+
+    // any of a set of types
+    assert!(extra_state
+        .path()
+        .matches_selector(&"$(user|exception).extra".parse().unwrap()));
+    assert!(!extra_state
+        .path()
+        .matches_selector(&"$(exception|thread).extra".parse().unwrap()));
+
     // AND/OR/NOT
     // (conjunction/disjunction/negation)
     assert!(foo_state
@@ -716,3 +1719,875 @@ fn test_path_matching() {
         .path()
         .matches_selector(&"(~$object.**)".parse().unwrap()));
 }
+
+#[test]
+fn test_path_matching_at() {
+    fn matches_at(state: &ProcessingState<'_>, selector: &str) -> Option<usize> {
+        let selector: SelectorSpec = selector.parse().unwrap();
+        let depth = state.path().matches_selector_at(&selector);
+        assert_eq!(depth.is_some(), state.path().matches_selector(&selector));
+        depth
+    }
+
+    with_user_extra_foo(|[_, user_state, extra_state, foo_state, zero_state]| {
+        // fast path: leaf matches anchor at the current state
+        assert_eq!(matches_at(extra_state, "user.extra"), Some(2));
+        assert_eq!(matches_at(zero_state, "$user.extra.foo.0"), Some(4));
+        assert_eq!(matches_at(zero_state, "$user.extra.foo.*"), Some(4));
+        assert_eq!(matches_at(zero_state, "$user.extra.foo.1"), None);
+        assert_eq!(matches_at(user_state, "user.extra"), None);
+
+        // slow path: deep wildcards anchor at the item before the wildcard
+        assert_eq!(matches_at(zero_state, "$event.**"), Some(0));
+        assert_eq!(matches_at(zero_state, "$user.**"), Some(1));
+        assert_eq!(matches_at(zero_state, "$user.extra.**"), Some(2));
+        assert_eq!(matches_at(zero_state, "$user.extra.foo.**"), Some(3));
+        assert_eq!(matches_at(zero_state, "$user.**.0"), Some(1));
+        assert_eq!(matches_at(zero_state, "$user.$object.**.0"), Some(2));
+        assert_eq!(matches_at(foo_state, "**.$array"), Some(0));
+        assert_eq!(matches_at(zero_state, "$user.extra.bar.**"), None);
+        assert_eq!(matches_at(zero_state, "$user.**.1"), None);
+
+        // AND/OR/NOT
+        assert_eq!(matches_at(zero_state, "$user.** & $user.extra.**"), Some(2));
+        assert_eq!(matches_at(zero_state, "absolutebogus | $user.**"), Some(1));
+        assert_eq!(matches_at(zero_state, "~$object"), Some(4));
+        assert_eq!(matches_at(zero_state, "~$object.**"), None);
+        assert_eq!(matches_at(zero_state, "$object | absolutebogus"), None);
+    });
+}
+
+#[test]
+fn test_field_attrs_with_overrides() {
+    let attrs = FieldAttrs::new().retain(true).max_chars(MaxChars::Symbol);
+    let overridden = attrs.with_overrides(FieldAttrsPatch {
+        pii: Some(Pii::True),
+        max_chars: Some(MaxChars::Message),
+        ..Default::default()
+    });
+
+    assert_eq!(overridden.pii, Pii::True);
+    assert_eq!(overridden.max_chars, Some(MaxChars::Message));
+    assert!(overridden.retain);
+
+    // the original attributes are left untouched
+    assert_eq!(attrs.pii, Pii::False);
+    assert_eq!(attrs.max_chars, Some(MaxChars::Symbol));
+}
+
+#[test]
+fn test_with_path_str() {
+    for path in &["", "user", "user.extra.foo.0", "0.foo.10"] {
+        let rendered = ProcessingState::with_path_str(path, |state| state.path().to_string());
+        assert_eq!(rendered, *path);
+    }
+
+    ProcessingState::with_path_str("user.extra.foo.0", |state| {
+        assert_eq!(state.depth(), 4);
+        assert_eq!(state.path().index(), Some(0));
+        assert!(state
+            .path()
+            .matches_selector(&"user.extra.foo.0".parse().unwrap()));
+    });
+}
+
+/// Calls `f` with the states from the root down to `user.extra.foo.0`.
+///
+/// The root is typed as event, `user` as user, `extra` as object and `foo` as array.
+#[cfg(test)]
+fn with_user_extra_foo<F, R>(f: F) -> R
+where
+    F: FnOnce([&ProcessingState<'_>; 5]) -> R,
+{
+    let segments = [
+        PathSegment::Key("user".to_owned()),
+        PathSegment::Key("extra".to_owned()),
+        PathSegment::Key("foo".to_owned()),
+        PathSegment::Index(0),
+    ];
+    let value_types = [
+        Some(ValueType::User),
+        Some(ValueType::Object),
+        Some(ValueType::Array),
+        None,
+    ];
+
+    ProcessingState::with_path(
+        &segments,
+        Some(ValueType::Event),
+        &value_types,
+        |zero_state| {
+            let foo_state = zero_state.parent().unwrap();
+            let extra_state = foo_state.parent().unwrap();
+            let user_state = extra_state.parent().unwrap();
+            let event_state = user_state.parent().unwrap();
+            f([event_state, user_state, extra_state, foo_state, zero_state])
+        },
+    )
+}
+
+#[test]
+fn test_max_chars_names() {
+    let named = [
+        MaxChars::Hash,
+        MaxChars::EnumLike,
+        MaxChars::Summary,
+        MaxChars::Message,
+        MaxChars::Symbol,
+        MaxChars::Path,
+        MaxChars::ShortPath,
+        MaxChars::Logger,
+        MaxChars::Email,
+        MaxChars::Culprit,
+        MaxChars::TagKey,
+        MaxChars::TagValue,
+        MaxChars::Environment,
+    ];
+
+    for &max_chars in &named {
+        let name = max_chars.name().unwrap();
+        assert_eq!(MaxChars::from_name(name), Some(max_chars));
+    }
+
+    assert_eq!(MaxChars::from_name("tagvalue"), Some(MaxChars::TagValue));
+    assert_eq!(MaxChars::from_name("bogus"), None);
+    assert_eq!(MaxChars::Hard(42).name(), None);
+    assert_eq!(MaxChars::Soft(42).name(), None);
+}
+
+#[test]
+fn test_value_type_containers() {
+    let containers = [
+        ValueType::Array,
+        ValueType::Object,
+        ValueType::Event,
+        ValueType::Attachments,
+        ValueType::Exception,
+        ValueType::Stacktrace,
+        ValueType::Frame,
+        ValueType::Request,
+        ValueType::User,
+        ValueType::LogEntry,
+        ValueType::Thread,
+        ValueType::Breadcrumb,
+        ValueType::Span,
+        ValueType::ClientSdkInfo,
+        ValueType::Minidump,
+    ];
+
+    let scalars = [
+        ValueType::String,
+        ValueType::Binary,
+        ValueType::Number,
+        ValueType::Boolean,
+        ValueType::DateTime,
+        ValueType::Message,
+        ValueType::HeapMemory,
+        ValueType::StackMemory,
+    ];
+
+    for &value_type in &containers {
+        assert!(value_type.is_container(), "{} is a container", value_type);
+        assert!(!value_type.is_scalar(), "{} is not a scalar", value_type);
+    }
+
+    for &value_type in &scalars {
+        assert!(value_type.is_scalar(), "{} is a scalar", value_type);
+        assert!(
+            !value_type.is_container(),
+            "{} is not a container",
+            value_type
+        );
+    }
+}
+
+#[test]
+fn test_value_type_for_annotated_or() {
+    let present = Annotated::new("foo".to_owned());
+    assert_eq!(
+        ValueType::for_annotated_or(&present, ValueType::Message),
+        ValueType::String
+    );
+
+    let absent = Annotated::<String>::empty();
+    assert_eq!(ValueType::for_field(&absent), None);
+    assert_eq!(
+        ValueType::for_annotated_or(&absent, ValueType::String),
+        ValueType::String
+    );
+
+    let untyped = Annotated::new(uuid::Uuid::nil());
+    assert_eq!(
+        ValueType::for_annotated_or(&untyped, ValueType::String),
+        ValueType::String
+    );
+}
+
+#[test]
+fn test_is_inside() {
+    with_user_extra_foo(|[event_state, _, _, foo_state, zero_state]| {
+        assert!(zero_state.is_inside(ValueType::User));
+        assert!(zero_state.is_inside(ValueType::Event));
+        assert!(zero_state.is_inside(ValueType::Array));
+        assert!(!zero_state.is_inside(ValueType::Exception));
+
+        // the state itself does not count
+        assert!(!foo_state.is_inside(ValueType::Array));
+        assert!(!event_state.is_inside(ValueType::Event));
+
+        // neither do newtypes of the state
+        let newtype_state = foo_state.enter_nothing(None);
+        assert!(!newtype_state.is_inside(ValueType::Array));
+        assert!(newtype_state.is_inside(ValueType::Object));
+    });
+}
+
+#[test]
+fn test_check_presence() {
+    let attrs = FieldAttrs::new().required(true).nonempty(true);
+    assert_eq!(
+        attrs.check_presence(false, false),
+        PresenceResult::MissingRequired
+    );
+    assert_eq!(
+        attrs.check_presence(true, true),
+        PresenceResult::EmptyNotAllowed
+    );
+    assert_eq!(attrs.check_presence(true, false), PresenceResult::Ok);
+
+    // neither required nor nonempty
+    let attrs = FieldAttrs::new();
+    assert_eq!(attrs.check_presence(false, false), PresenceResult::Ok);
+    assert_eq!(attrs.check_presence(true, true), PresenceResult::Ok);
+
+    // empty values pass the required check
+    let attrs = FieldAttrs::new().required(true);
+    assert_eq!(attrs.check_presence(true, true), PresenceResult::Ok);
+
+    // missing values pass the nonempty check
+    let attrs = FieldAttrs::new().nonempty(true);
+    assert_eq!(attrs.check_presence(false, false), PresenceResult::Ok);
+}
+
+#[test]
+fn test_max_chars_truncate() {
+    // exactly at the limit, but more bytes than characters
+    let value = "äöüßé";
+    assert!(matches!(
+        MaxChars::Hard(5).truncate(value),
+        Cow::Borrowed(_)
+    ));
+
+    // one character above the limit
+    let value = "äöüßéà";
+    assert_eq!(MaxChars::Hard(5).truncate(value), "äö...");
+
+    // the allowance is tolerated, but truncation goes down to the limit
+    let value = "äöüßéàèìòùáéíóú";
+    assert_eq!(MaxChars::Soft(5).truncate(value), value);
+    let value = "äöüßéàèìòùáéíóúx";
+    assert_eq!(MaxChars::Soft(5).truncate(value), "äö...");
+}
+
+#[test]
+fn test_max_chars_truncate_with() {
+    let value = "äöüßéà";
+    assert_eq!(MaxChars::Hard(5).truncate_with(value, "..."), "äö...");
+    assert_eq!(MaxChars::Hard(5).truncate_with(value, "…"), "äöüß…");
+    assert_eq!(MaxChars::Hard(5).truncate_with(value, ""), "äöüßé");
+
+    // the marker is cut off if it exceeds the limit on its own
+    assert_eq!(MaxChars::Hard(2).truncate_with(value, "..."), "..");
+
+    // values within the limit are never marked
+    assert_eq!(MaxChars::Hard(6).truncate_with(value, "…"), value);
+}
+
+#[test]
+fn test_path_matching_index_insensitive() {
+    with_user_extra_foo(|[_, _, _, foo_state, zero_state]| {
+        let selector: SelectorSpec = "extra.foo".parse().unwrap();
+        let insensitive = SelectorSpec::IndexInsensitive(Box::new(selector.clone()));
+
+        assert!(foo_state.path().matches_selector(&selector));
+        assert!(foo_state.path().matches_selector(&insensitive));
+        assert!(!zero_state.path().matches_selector(&selector));
+        assert!(zero_state.path().matches_selector(&insensitive));
+
+        // indices in the selector itself no longer match
+        let selector: SelectorSpec = "extra.foo.0".parse().unwrap();
+        let insensitive = SelectorSpec::IndexInsensitive(Box::new(selector.clone()));
+        assert!(zero_state.path().matches_selector(&selector));
+        assert!(!zero_state.path().matches_selector(&insensitive));
+
+        // deep wildcards and combinators are supported
+        let insensitive =
+            SelectorSpec::IndexInsensitive(Box::new("$user.** && !extra.bar".parse().unwrap()));
+        assert!(zero_state.path().matches_selector(&insensitive));
+        assert_eq!(insensitive.to_string(), "$noindex($user.** && !extra.bar)");
+        assert_eq!(
+            insensitive,
+            "$noindex($user.** && !extra.bar)".parse().unwrap()
+        );
+    });
+}
+
+#[test]
+fn test_describe() {
+    with_user_extra_foo(|[event_state, _, _, _, zero_state]| {
+        assert_eq!(event_state.describe(), "event");
+        assert_eq!(
+            zero_state.describe(),
+            "event > user(user) > extra(object) > foo(array) > 0"
+        );
+        assert_eq!(
+            ProcessingState::root()
+                .enter_index(1, None, None)
+                .describe(),
+            "root > 1"
+        );
+    });
+}
+
+#[test]
+fn test_filter_additional() {
+    let keys = ["foo", "bar"];
+
+    let attrs = FieldAttrs::new().retain(true);
+    assert!(attrs.filter_additional(keys.iter().copied()).is_empty());
+
+    let attrs = FieldAttrs::new();
+    assert_eq!(attrs.filter_additional(keys.iter().copied()), keys);
+}
+
+#[test]
+fn test_matches_value() {
+    let attrs = FieldAttrs {
+        match_regex: Some(r"^[^\n]+\z"),
+        ..FieldAttrs::new()
+    };
+    assert!(attrs.matches_value("foo"));
+    assert!(attrs.validate_value("foo").is_ok());
+    assert!(!attrs.matches_value("foo\nbar"));
+    assert!(attrs.validate_value("foo\nbar").is_err());
+
+    let attrs = FieldAttrs::new();
+    assert!(attrs.matches_value("foo\nbar"));
+    assert!(attrs.validate_value("foo\nbar").is_ok());
+}
+
+#[test]
+fn test_parent() {
+    // built by hand, since `with_user_extra_foo` relies on `parent`
+    let event_state = ProcessingState::new_root(None, Some(ValueType::Event)); // .
+    let user_state = event_state.enter_static("user", None, Some(ValueType::User)); // .user
+    let extra_state = user_state.enter_static("extra", None, Some(ValueType::Object)); // .user.extra
+
+    let parent = extra_state.parent().unwrap();
+    assert_eq!(parent.path().to_string(), "user");
+    assert_eq!(parent.value_type(), Some(ValueType::User));
+    assert!(event_state.parent().is_none());
+}
+
+#[test]
+fn test_defaults_for() {
+    assert_eq!(
+        FieldAttrs::defaults_for(ValueType::Message).max_chars,
+        Some(MaxChars::Message)
+    );
+    for &value_type in &[ValueType::String, ValueType::Frame] {
+        let attrs = FieldAttrs::defaults_for(value_type);
+        assert_eq!(attrs.max_chars, None);
+        assert_eq!(attrs.bag_size, None);
+        assert_eq!(attrs.pii, Pii::False);
+    }
+}
+
+#[test]
+fn test_max_chars_from_str() {
+    for &max_chars in &[
+        MaxChars::Logger,
+        MaxChars::ShortPath,
+        MaxChars::Hard(200),
+        MaxChars::Soft(42),
+    ] {
+        assert_eq!(
+            max_chars.to_string().parse::<MaxChars>().unwrap(),
+            max_chars
+        );
+    }
+
+    assert_eq!("hard:200".parse::<MaxChars>().unwrap(), MaxChars::Hard(200));
+    assert_eq!("soft:200".parse::<MaxChars>().unwrap(), MaxChars::Soft(200));
+    assert!(matches!(
+        "hard:lots".parse::<MaxChars>(),
+        Err(ParseMaxCharsError::InvalidLength(_))
+    ));
+    assert!(matches!(
+        "huge".parse::<MaxChars>(),
+        Err(ParseMaxCharsError::UnknownName(_))
+    ));
+}
+
+#[test]
+fn test_bag_size_from_str() {
+    for &bag_size in &[
+        BagSize::Small,
+        BagSize::Medium,
+        BagSize::Large,
+        BagSize::Larger,
+        BagSize::Massive,
+    ] {
+        assert_eq!(bag_size.to_string().parse::<BagSize>().unwrap(), bag_size);
+    }
+
+    assert!("tiny".parse::<BagSize>().is_err());
+}
+
+#[test]
+fn test_path_matching_traced() {
+    with_user_extra_foo(|[_, _, extra_state, _, _]| {
+        let selector = "$user.extra".parse().unwrap();
+        let (matches, steps) = extra_state.path().matches_selector_traced(&selector);
+        assert!(matches);
+        assert_eq!(
+            steps,
+            vec![
+                MatchStep {
+                    item: SelectorPathItem::Key("extra".to_owned()),
+                    path: "user.extra".to_owned(),
+                    matched: true,
+                },
+                MatchStep {
+                    item: SelectorPathItem::Type(ValueType::User),
+                    path: "user".to_owned(),
+                    matched: true,
+                },
+            ]
+        );
+
+        let selector = "$event.extra".parse().unwrap();
+        let (matches, steps) = extra_state.path().matches_selector_traced(&selector);
+        assert!(!matches);
+        assert_eq!(steps.len(), 2);
+        assert!(!steps[1].matched);
+    });
+}
+
+#[test]
+fn test_to_bracket_notation() {
+    with_user_extra_foo(|[event_state, _, _, _, zero_state]| {
+        let bar_state = zero_state.enter_static("bar", None, None); // .user.extra.foo.0.bar
+
+        assert_eq!(zero_state.path().to_bracket_notation(), "user.extra.foo[0]");
+        assert_eq!(
+            bar_state.path().to_bracket_notation(),
+            "user.extra.foo[0].bar"
+        );
+        assert_eq!(zero_state.path().to_string(), "user.extra.foo.0");
+        assert_eq!(event_state.path().to_bracket_notation(), "");
+    });
+}
+
+#[test]
+fn test_path_starts_with() {
+    with_user_extra_foo(|[_, user_state, extra_state, _, zero_state]| {
+        let user = PathSegment::Key("user".to_owned());
+        let extra = PathSegment::Key("extra".to_owned());
+        let other = PathSegment::Key("other".to_owned());
+
+        assert!(zero_state.path().starts_with(&[]));
+        assert!(zero_state
+            .path()
+            .starts_with(&[user.clone(), extra.clone()]));
+        assert!(extra_state
+            .path()
+            .starts_with(&[user.clone(), extra.clone()]));
+
+        // the prefix is longer than the path
+        assert!(!user_state.path().starts_with(&[user.clone(), extra]));
+
+        // the prefix diverges after the first segment
+        assert!(!zero_state.path().starts_with(&[user, other]));
+        assert!(!zero_state.path().starts_with(&[PathSegment::Index(0)]));
+    });
+}
+
+#[test]
+fn test_value_type_typical_parent() {
+    assert_eq!(
+        ValueType::Frame.typical_parent(),
+        Some(ValueType::Stacktrace)
+    );
+    assert_eq!(
+        ValueType::Stacktrace.typical_parent(),
+        Some(ValueType::Exception)
+    );
+    assert_eq!(
+        ValueType::Exception.typical_parent(),
+        Some(ValueType::Event)
+    );
+    assert_eq!(
+        ValueType::Breadcrumb.typical_parent(),
+        Some(ValueType::Event)
+    );
+    assert_eq!(ValueType::Event.typical_parent(), None);
+    assert_eq!(ValueType::String.typical_parent(), None);
+}
+
+#[test]
+fn test_pii_effective() {
+    assert!(Pii::True.effective(true));
+    assert!(Pii::True.effective(false));
+    assert!(!Pii::False.effective(true));
+    assert!(!Pii::False.effective(false));
+    assert!(Pii::Maybe.effective(true));
+    assert!(!Pii::Maybe.effective(false));
+}
+
+#[test]
+fn test_value_type_aliases() {
+    let value_types = [
+        ValueType::String,
+        ValueType::Binary,
+        ValueType::Number,
+        ValueType::Boolean,
+        ValueType::DateTime,
+        ValueType::Array,
+        ValueType::Object,
+        ValueType::Event,
+        ValueType::Attachments,
+        ValueType::Exception,
+        ValueType::Stacktrace,
+        ValueType::Frame,
+        ValueType::Request,
+        ValueType::User,
+        ValueType::LogEntry,
+        ValueType::Message,
+        ValueType::Thread,
+        ValueType::Breadcrumb,
+        ValueType::Span,
+        ValueType::ClientSdkInfo,
+        ValueType::Minidump,
+        ValueType::HeapMemory,
+        ValueType::StackMemory,
+    ];
+
+    for &value_type in &value_types {
+        let aliases = value_type.aliases();
+        assert_eq!(aliases[0], value_type.as_str());
+        for alias in aliases {
+            assert_eq!(alias.parse::<ValueType>().unwrap(), value_type);
+        }
+    }
+
+    assert_eq!(ValueType::Exception.aliases(), &["error", "exception"]);
+}
+
+#[test]
+fn test_current_index() {
+    with_user_extra_foo(|[event_state, user_state, _, _, zero_state]| {
+        assert_eq!(zero_state.current_index(), Some(0));
+        assert_eq!(user_state.current_index(), None);
+        assert_eq!(event_state.current_index(), None);
+    });
+}
+
+#[test]
+fn test_path_matching_root() {
+    let matches = |path: &str, selector: &str| {
+        let selector: SelectorSpec = selector.parse().unwrap();
+        ProcessingState::with_path_str(path, |state| state.path().matches_selector(&selector))
+    };
+
+    assert!(matches("", "$"));
+    assert!(!matches("user", "$"));
+
+    // anchored paths only match at the top level
+    assert!(matches("user", "$.user"));
+    assert!(!matches("user.extra.user", "$.user"));
+    assert!(matches("user.extra.user", "user"));
+
+    assert!(matches("user.extra.user", "$.user.**"));
+    assert!(!matches("user.extra.user", "$.extra.**"));
+}
+
+#[test]
+fn test_path_matching_empty_combinators() {
+    let matches = |path: &str, selector: &SelectorSpec| {
+        ProcessingState::with_path_str(path, |state| state.path().matches_selector(selector))
+    };
+
+    let empty_and = SelectorSpec::And(vec![]);
+    assert!(matches("", &empty_and));
+    assert!(matches("user", &empty_and));
+    assert!(!empty_and.is_specific());
+
+    let empty_or = SelectorSpec::Or(vec![]);
+    assert!(!matches("", &empty_or));
+    assert!(!matches("user", &empty_or));
+}
+
+#[test]
+fn test_path_matching_literal() {
+    let paths = [
+        "",
+        "user",
+        "user.extra",
+        "user.extra.Föo",
+        "user.extra.Föo.0",
+    ];
+
+    // tracing disables the literal fast path, so results must agree with the general matcher
+    for selector in &[
+        "user",
+        "USER.extra",
+        "extra.'föo'",
+        "'föo'.0",
+        "'föo'.1",
+        "user.'föo'",
+        "0",
+    ] {
+        let selector: SelectorSpec = selector.parse().unwrap();
+        for path in &paths {
+            ProcessingState::with_path_str(path, |state| {
+                let path = state.path();
+                let (traced, _) = path.matches_selector_traced(&selector);
+                assert_eq!(
+                    path.matches_selector(&selector),
+                    traced,
+                    "{} on {}",
+                    selector,
+                    path
+                );
+            });
+        }
+    }
+
+    ProcessingState::with_path_str("user.extra.Föo.0", |state| {
+        assert!(state
+            .path()
+            .matches_selector(&"EXTRA.'FÖO'.0".parse().unwrap()));
+    });
+}
+
+#[test]
+fn test_child_pii() {
+    let root = ProcessingState::new_root(None, None);
+    for &pii in &[Pii::True, Pii::Maybe, Pii::False] {
+        let attrs = FieldAttrs::new().pii(pii);
+        let state = root.enter_static("foo", Some(Cow::Owned(attrs)), None);
+        assert_eq!(state.child_pii(), pii);
+
+        let child = state.enter_index(0, state.inner_attrs(), None);
+        assert_eq!(child.attrs().pii, pii);
+    }
+}
+
+#[test]
+fn test_path_matching_deep_wildcard_bounded() {
+    with_user_extra_foo(|[_, _, _, _, zero_state]| {
+        let within = |state: &ProcessingState<'_>, selector: &str| {
+            state.path().matches_selector(&selector.parse().unwrap())
+        };
+
+        // the wildcard covers extra and foo
+        assert!(within(zero_state, "user.**{2}.0"));
+        assert!(within(zero_state, "user.**{3}.0"));
+        assert!(!within(zero_state, "user.**{1}.0"));
+        assert!(within(zero_state, "user.**"));
+        assert!(!within(zero_state, "user.**{2}"));
+        assert!(within(zero_state, "user.**{3}"));
+
+        // without a prefix, the wildcard extends up to the root
+        assert!(within(zero_state, "**{3}.0"));
+        assert!(!within(zero_state, "**{2}.0"));
+
+        // the prefix does not need to be the first match from the root
+        let user2_state = zero_state.enter_static("user", None, Some(ValueType::User)); // .user.extra.foo.0.user
+        let bar_state = user2_state.enter_static("bar", None, None); // .user.extra.foo.0.user.bar
+        let baz_state = bar_state.enter_static("baz", None, None); // .user.extra.foo.0.user.bar.baz
+        assert!(within(&baz_state, "user.**{1}.baz"));
+        assert!(within(&baz_state, "$.user.**{6}.baz"));
+        assert!(!within(&baz_state, "$.user.**{4}.baz"));
+    });
+}
+
+#[test]
+fn test_value_type_pii_relevant() {
+    let relevant: Vec<_> = ValueType::pii_relevant_types().to_vec();
+    assert_eq!(
+        relevant,
+        vec![
+            ValueType::String,
+            ValueType::User,
+            ValueType::Request,
+            ValueType::LogEntry,
+            ValueType::Message,
+        ]
+    );
+
+    assert!(ValueType::User.is_pii_relevant());
+    assert!(!ValueType::Frame.is_pii_relevant());
+    assert!(!ValueType::Number.is_pii_relevant());
+}
+
+#[test]
+fn test_with_path() {
+    let segments = [
+        PathSegment::Key("user".to_owned()),
+        PathSegment::Key("extra".to_owned()),
+        PathSegment::Key("foo".to_owned()),
+        PathSegment::Index(0),
+    ];
+    let value_types = [Some(ValueType::User), None, Some(ValueType::Array), None];
+
+    ProcessingState::with_path(&segments, Some(ValueType::Event), &value_types, |state| {
+        assert_eq!(state.path().to_string(), "user.extra.foo.0");
+        assert_eq!(state.depth(), 4);
+
+        // every segment carries its own type
+        let types: Vec<_> = state.iter().map(ProcessingState::value_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                None,
+                Some(ValueType::Array),
+                None,
+                Some(ValueType::User),
+                Some(ValueType::Event),
+            ]
+        );
+        assert!(state
+            .path()
+            .matches_selector(&"$user.extra.*.0".parse().unwrap()));
+    });
+
+    let root_type =
+        ProcessingState::with_path(&[], Some(ValueType::Event), &[], |state| state.value_type());
+    assert_eq!(root_type, Some(ValueType::Event));
+}
+
+#[test]
+#[should_panic(expected = "every segment needs a value type")]
+fn test_with_path_missing_types() {
+    let segments = [PathSegment::Key("user".to_owned())];
+    ProcessingState::with_path(&segments, Some(ValueType::Event), &[], |_| ());
+}
+
+#[test]
+fn test_value_matching() {
+    ProcessingState::with_path_str("user.extra.foo", |foo_state| {
+        let path = foo_state.path();
+
+        let selector: SelectorSpec = "$value('^[^@\\s]+@[^@\\s]+$')".parse().unwrap();
+        assert!(path.matches_value_selector(Pii::True, &selector, "foo@example.com"));
+        assert!(!path.matches_value_selector(Pii::True, &selector, "not an email"));
+        assert!(!path.matches_selector(&selector));
+
+        // without a value, neither the predicate nor its negation matches
+        for selector in &[
+            "!$value('@')",
+            "!($value('@') && extra.*)",
+            "!(!$value('@'))",
+        ] {
+            assert!(!path.matches_selector(&selector.parse().unwrap()));
+        }
+        assert!(path.matches_selector(&"!$value('@') || extra.*".parse().unwrap()));
+        assert!(path.matches_value_selector(Pii::True, &"!$value('@')".parse().unwrap(), "foo"));
+
+        // value predicates are not specific
+        assert!(!path.matches_value_selector(Pii::Maybe, &selector, "foo@example.com"));
+
+        let selector: SelectorSpec = "$value('@') && extra.*".parse().unwrap();
+        assert!(path.matches_value_selector(Pii::True, &selector, "foo@example.com"));
+        let extra_path = foo_state.parent().unwrap().path();
+        assert!(!extra_path.matches_value_selector(Pii::True, &selector, "foo@example.com"));
+    });
+}
+
+#[test]
+fn test_max_chars_truncate_graphemes() {
+    // the family emoji is a single grapheme made of seven scalar values
+    let value = "ab\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{200d}\u{1f466}cdef";
+    assert_eq!(
+        MaxChars::Hard(6).truncate_graphemes(value),
+        "ab\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{200d}\u{1f466}..."
+    );
+    assert_eq!(MaxChars::Hard(7).truncate_graphemes(value), value);
+
+    // counting scalar values splits the emoji
+    assert_eq!(MaxChars::Hard(6).truncate(value), "ab\u{1f468}...");
+}
+
+#[test]
+fn test_path_ordering() {
+    with_user_extra_foo(|[_, user_state, extra_state, foo_state, zero_state]| {
+        let bar_state = extra_state.enter_static("bar", None, None); // .user.extra.bar
+        let index_state = extra_state.enter_index(0, None, None); // .user.extra.0
+
+        let mut paths = [
+            zero_state.path(),
+            index_state.path(),
+            foo_state.path(),
+            extra_state.path(),
+            bar_state.path(),
+        ];
+        paths.sort();
+
+        let rendered: Vec<_> = paths.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "user.extra",
+                "user.extra.bar",
+                "user.extra.foo",
+                "user.extra.foo.0",
+                "user.extra.0",
+            ]
+        );
+
+        // value types do not affect the comparison
+        ProcessingState::with_path_str("user", |untyped_user_state| {
+            assert_eq!(untyped_user_state.path(), user_state.path());
+        });
+    });
+}
+
+#[test]
+fn test_field_attrs_config_roundtrip() {
+    let attrs = FieldAttrs {
+        name: Some("release"),
+        match_regex: Some(r"^[^\r\n]*\z"),
+        ..FieldAttrs::new()
+            .required(true)
+            .trim_whitespace(true)
+            .max_chars(MaxChars::Hard(200))
+            .bag_size(BagSize::Small)
+            .pii(Pii::Maybe)
+    };
+
+    let config = FieldAttrsConfig::from(&attrs);
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        json,
+        r#"{"name":"release","required":true,"nonempty":false,"trim_whitespace":true,"match_regex":"^[^\\r\\n]*\\z","max_chars":"hard:200","bag_size":"small","pii":"maybe","retain":false}"#
+    );
+
+    let parsed: FieldAttrsConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, config);
+
+    let restored = parsed.to_field_attrs().unwrap();
+    assert_eq!(restored.name, None);
+    assert_eq!(restored.match_regex, attrs.match_regex);
+    assert_eq!(restored.max_chars, attrs.max_chars);
+    assert_eq!(restored.bag_size, attrs.bag_size);
+    assert_eq!(restored.pii, attrs.pii);
+    assert!(restored.required && restored.trim_whitespace);
+
+    let config = FieldAttrsConfig {
+        match_regex: Some("^foo$".to_owned()),
+        ..Default::default()
+    };
+    assert!(config.to_field_attrs().is_err());
+}
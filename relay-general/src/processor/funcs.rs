@@ -1,4 +1,4 @@
-use crate::processor::{ProcessValue, ProcessingState, Processor};
+use crate::processor::{FieldAttrs, ProcessValue, ProcessingState, Processor};
 use crate::types::{Annotated, ProcessingResult};
 
 /// Processes the value using the given processor.
@@ -22,3 +22,52 @@ where
 
     Ok(())
 }
+
+/// Trims leading and trailing whitespace if the field attributes ask for it.
+///
+/// Whitespace is as defined by the Unicode `White_Space` property. Returns `true` if the value
+/// was changed.
+pub fn apply_trim(value: &mut String, attrs: &FieldAttrs) -> bool {
+    if !attrs.trim_whitespace {
+        return false;
+    }
+
+    let original_len = value.len();
+
+    let end = value.trim_end().len();
+    value.truncate(end);
+
+    let start = value.len() - value.trim_start().len();
+    value.drain(..start);
+
+    value.len() != original_len
+}
+
+#[test]
+fn test_apply_trim() {
+    let attrs = FieldAttrs::new().trim_whitespace(true);
+
+    let mut value = " \u{3000}foo bar\t\n".to_owned();
+    assert!(apply_trim(&mut value, &attrs));
+    assert_eq!(value, "foo bar");
+
+    assert!(!apply_trim(&mut value, &attrs));
+    assert_eq!(value, "foo bar");
+
+    let mut value = " \t ".to_owned();
+    assert!(apply_trim(&mut value, &attrs));
+    assert_eq!(value, "");
+}
+
+#[test]
+fn test_apply_trim_disabled() {
+    let attrs = FieldAttrs::new();
+
+    let mut value = "  foo  ".to_owned();
+    assert!(!apply_trim(&mut value, &attrs));
+    assert_eq!(value, "  foo  ");
+
+    let mut value = " \t ".to_owned();
+    assert!(!apply_trim(&mut value, &attrs));
+    assert_eq!(value, " \t ");
+}
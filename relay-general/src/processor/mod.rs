@@ -9,10 +9,15 @@ mod size;
 mod traits;
 
 pub use self::attrs::{
-    BagSize, FieldAttrs, MaxChars, Path, Pii, ProcessingState, UnknownValueTypeError, ValueType,
+    BagSize, FieldAttrs, FieldAttrsConfig, FieldAttrsPatch, MatchStep, MaxChars,
+    ParseMaxCharsError, Path, PathSegment, Pii, PresenceResult, ProcessingState, RegexMismatch,
+    UndeclaredRegexError, UnknownBagSizeError, UnknownPiiError, UnknownValueTypeError, ValueType,
 };
 pub use self::chunks::{join_chunks, process_chunked_value, split_chunks, Chunk};
-pub use self::funcs::process_value;
-pub use self::selector::{SelectorPathItem, SelectorSpec};
+pub use self::funcs::{apply_trim, process_value};
+pub use self::selector::{
+    SelectorLintWarning, SelectorPathItem, SelectorSpec, ValuePredicate,
+    DEFAULT_MAX_SELECTOR_PATH_LEN,
+};
 pub use self::size::{estimate_size, estimate_size_flat};
 pub use self::traits::{ProcessValue, Processor};
@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::fmt;
 use std::str::FromStr;
 
@@ -5,8 +6,11 @@ use failure::Fail;
 use pest::error::Error;
 use pest::iterators::Pair;
 use pest::Parser;
+use regex::Regex;
+use smallvec::SmallVec;
 
-use crate::processor::{ProcessingState, ValueType};
+use crate::processor::attrs::PathItem;
+use crate::processor::{Path, PathSegment, Pii, ProcessingState, ValueType};
 
 /// Error for invalid selectors
 #[derive(Debug, Fail)]
@@ -26,6 +30,15 @@ pub enum InvalidSelectorError {
     #[fail(display = "invalid selector: unknown value")]
     UnknownType,
 
+    #[fail(display = "invalid selector: path longer than {} items", _0)]
+    PathTooLong(usize),
+
+    #[fail(display = "invalid selector: root must be the first item of a path")]
+    InvalidRoot,
+
+    #[fail(display = "invalid selector: invalid value pattern")]
+    InvalidPattern(#[cause] regex::Error),
+
     #[fail(display = "parser bug: consumed {} (expected {})", _0, _1)]
     UnexpectedToken(String, &'static str),
 }
@@ -43,16 +56,33 @@ use self::parser::{Rule, SelectorParser};
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum SelectorPathItem {
     Type(ValueType),
+    TypeSet(SmallVec<[ValueType; 4]>),
+    AnyType,
+    Root,
     Index(usize),
     Key(String),
     Wildcard,
     DeepWildcard,
+    /// A deep wildcard that spans at most the given number of levels, written as `**{3}`.
+    DeepWildcardBounded(usize),
 }
 
 impl fmt::Display for SelectorPathItem {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             SelectorPathItem::Type(ty) => write!(f, "${}", ty),
+            SelectorPathItem::TypeSet(ref types) => {
+                write!(f, "$(")?;
+                for (idx, ty) in types.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, "|")?;
+                    }
+                    write!(f, "{}", ty)?;
+                }
+                write!(f, ")")
+            }
+            SelectorPathItem::AnyType => write!(f, "$*"),
+            SelectorPathItem::Root => write!(f, "$"),
             SelectorPathItem::Index(index) => write!(f, "{}", index),
             SelectorPathItem::Key(ref key) => {
                 if key_needs_quoting(key) {
@@ -63,6 +93,7 @@ impl fmt::Display for SelectorPathItem {
             }
             SelectorPathItem::Wildcard => write!(f, "*"),
             SelectorPathItem::DeepWildcard => write!(f, "**"),
+            SelectorPathItem::DeepWildcardBounded(max_levels) => write!(f, "**{{{}}}", max_levels),
         }
     }
 }
@@ -72,76 +103,301 @@ impl SelectorPathItem {
         match *self {
             SelectorPathItem::Wildcard => true,
             SelectorPathItem::DeepWildcard => true,
+            SelectorPathItem::DeepWildcardBounded(_) => true,
             SelectorPathItem::Type(ty) => state.value_type() == Some(ty),
+            SelectorPathItem::TypeSet(ref types) => state
+                .value_type()
+                .map(|ty| types.contains(&ty))
+                .unwrap_or(false),
+            SelectorPathItem::AnyType => state.value_type().is_some(),
+            SelectorPathItem::Root => state.depth() == 0,
             SelectorPathItem::Index(idx) => state.path().index() == Some(idx),
             SelectorPathItem::Key(ref key) => state
                 .path()
                 .key()
-                .map(|k| k.to_lowercase() == key.to_lowercase())
+                .map(|k| key_matches(k, key))
                 .unwrap_or(false),
         }
     }
+
+    /// Returns `true` for deep wildcards, including bounded ones.
+    pub(super) fn is_deep_wildcard(&self) -> bool {
+        match *self {
+            SelectorPathItem::DeepWildcard | SelectorPathItem::DeepWildcardBounded(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` for keys and indices, which match a single path segment by value.
+    pub(super) fn is_literal(&self) -> bool {
+        match *self {
+            SelectorPathItem::Key(_) | SelectorPathItem::Index(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Compares a path key with a selector key case-insensitively.
+pub(super) fn key_matches(key: &str, selector_key: &str) -> bool {
+    // avoid allocating in the common case of ascii keys
+    if key.is_ascii() && selector_key.is_ascii() {
+        key.eq_ignore_ascii_case(selector_key)
+    } else {
+        key.to_lowercase() == selector_key.to_lowercase()
+    }
+}
+
+/// A regular expression that matches the value of a field, see `SelectorSpec::ValueMatches`.
+///
+/// Predicates compare and order by their pattern.
+#[derive(Clone, Debug)]
+pub struct ValuePredicate(Regex);
+
+impl ValuePredicate {
+    /// Compiles a predicate from a regular expression.
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(ValuePredicate)
+    }
+
+    /// Returns the pattern of this predicate.
+    pub fn pattern(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Returns `true` if the value matches the pattern anywhere.
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+}
+
+impl PartialEq for ValuePredicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern() == other.pattern()
+    }
+}
+
+impl Eq for ValuePredicate {}
+
+impl PartialOrd for ValuePredicate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ValuePredicate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.pattern().cmp(other.pattern())
+    }
+}
+
+impl fmt::Display for ValuePredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$value('{}')", self.pattern().replace("'", "''"))
+    }
+}
+
+/// The default maximum number of items in a selector path, see `SelectorSpec::parse_with_limit`.
+pub const DEFAULT_MAX_SELECTOR_PATH_LEN: usize = 64;
+
+/// A potential mistake in a selector, as reported by `SelectorSpec::lint`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SelectorLintWarning {
+    /// A conjunction contains a selector and its negation, so it never matches.
+    Contradiction(SelectorSpec),
+    /// A conjunction without selectors, which matches everything.
+    EmptyAnd,
+    /// A disjunction without selectors, which never matches.
+    EmptyOr,
+    /// A deep wildcard makes the selector non-specific, so it skips fields with `pii=maybe`.
+    NonSpecificDeepWildcard,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum SelectorSpec {
+    /// Matches if all inner selectors match.
+    ///
+    /// An empty conjunction is vacuously true and matches every path. Since it does not address
+    /// a location, it is not specific.
     And(Vec<SelectorSpec>),
+    /// Matches if any of the inner selectors matches.
+    ///
+    /// An empty disjunction never matches.
     Or(Vec<SelectorSpec>),
     Not(Box<SelectorSpec>),
     Path(Vec<SelectorPathItem>),
+    /// Matches the inner selector while treating array indices as transparent, written as
+    /// `$noindex(...)`.
+    ///
+    /// For instance, `$noindex(extra.foo)` matches both `.extra.foo` and `.extra.foo.0`. Index
+    /// items in the inner selector never match.
+    IndexInsensitive(Box<SelectorSpec>),
+    /// Matches values that match a pattern, regardless of their path, written as `$value('...')`.
+    ///
+    /// This only applies when the value is known, see `Path::matches_value_selector`. Otherwise,
+    /// neither the predicate nor its negation matches. In particular, this never matches
+    /// containers.
+    ValueMatches(ValuePredicate),
 }
 
 impl SelectorSpec {
+    /// Creates a path selector that addresses the given path by its keys and indices.
+    ///
+    /// Like all path selectors, the result matches paths that end in the same segments, but no
+    /// paths that are shallower or deeper. Returns `None` for the root path, which has no segments.
+    pub fn exact_for_path(path: &Path<'_>) -> Option<Self> {
+        let items = path.items();
+        if items.is_empty() {
+            return None;
+        }
+
+        let items = items
+            .into_iter()
+            .map(|item| match *item {
+                PathItem::StaticKey(key) => SelectorPathItem::Key(key.to_owned()),
+                PathItem::Index(index) => SelectorPathItem::Index(index),
+            })
+            .collect();
+
+        Some(SelectorSpec::Path(items))
+    }
+
+    /// Checks if the given path matches this selector, as it would during PII processing.
+    ///
+    /// `segments` addresses the value from the root. `root_type` is the value type of the root,
+    /// and `types[i]` is the value type of the value at `segments[i]`. `pii` is the PII attribute
+    /// of the addressed field: fields with `Pii::False` never match, and fields with `Pii::Maybe`
+    /// only match specific selectors.
+    ///
+    /// This gives the same result as `Path::matches_selector` on an equivalent processing state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `segments` and `types` have different lengths.
+    pub fn matches_path(
+        &self,
+        segments: &[PathSegment],
+        root_type: Option<ValueType>,
+        types: &[Option<ValueType>],
+        pii: Pii,
+    ) -> bool {
+        if !pii.effective(self.is_specific()) {
+            return false;
+        }
+
+        ProcessingState::with_path(segments, root_type, types, |state| {
+            state.path().matches_selector(self)
+        })
+    }
+
+    /// Parses a selector, rejecting paths with more than `max_path_len` items.
+    ///
+    /// Parsing via `FromStr` applies `DEFAULT_MAX_SELECTOR_PATH_LEN`.
+    pub fn parse_with_limit(s: &str, max_path_len: usize) -> Result<Self, InvalidSelectorError> {
+        let selector = parse_selector(s)?;
+        if selector.max_path_len() > max_path_len {
+            return Err(InvalidSelectorError::PathTooLong(max_path_len));
+        }
+
+        Ok(selector)
+    }
+
+    /// Returns the number of items in the longest path of this selector.
+    pub fn max_path_len(&self) -> usize {
+        match *self {
+            SelectorSpec::And(ref selectors) | SelectorSpec::Or(ref selectors) => selectors
+                .iter()
+                .map(SelectorSpec::max_path_len)
+                .max()
+                .unwrap_or(0),
+            SelectorSpec::Not(ref selector) | SelectorSpec::IndexInsensitive(ref selector) => {
+                selector.max_path_len()
+            }
+            SelectorSpec::Path(ref path) => path.len(),
+            SelectorSpec::ValueMatches(_) => 0,
+        }
+    }
+
+    /// Checks the selector for obvious mistakes.
+    ///
+    /// This is a purely static analysis of the selector and does not catch all selectors that
+    /// never match.
+    pub fn lint(&self) -> Vec<SelectorLintWarning> {
+        fn walk(selector: &SelectorSpec, warnings: &mut Vec<SelectorLintWarning>) -> bool {
+            let mut has_deep_wildcard = false;
+            match *selector {
+                SelectorSpec::And(ref selectors) => {
+                    if selectors.is_empty() {
+                        warnings.push(SelectorLintWarning::EmptyAnd);
+                    }
+
+                    for x in selectors {
+                        let negation = SelectorSpec::Not(Box::new(x.clone()));
+                        if selectors.contains(&negation) {
+                            warnings.push(SelectorLintWarning::Contradiction(x.clone()));
+                        }
+                        has_deep_wildcard |= walk(x, warnings);
+                    }
+                }
+                SelectorSpec::Or(ref selectors) => {
+                    if selectors.is_empty() {
+                        warnings.push(SelectorLintWarning::EmptyOr);
+                    }
+
+                    for x in selectors {
+                        has_deep_wildcard |= walk(x, warnings);
+                    }
+                }
+                SelectorSpec::Not(ref x) | SelectorSpec::IndexInsensitive(ref x) => {
+                    has_deep_wildcard = walk(x, warnings);
+                }
+                SelectorSpec::Path(ref path) => {
+                    has_deep_wildcard = path.iter().any(SelectorPathItem::is_deep_wildcard);
+                }
+                SelectorSpec::ValueMatches(_) => (),
+            }
+            has_deep_wildcard
+        }
+
+        let mut warnings = Vec::new();
+        if walk(self, &mut warnings) && !self.is_specific() {
+            warnings.push(SelectorLintWarning::NonSpecificDeepWildcard);
+        }
+        warnings
+    }
+
     /// A selector is specific if it directly addresses a single event location by path. We use
     /// this distinction in the PII processor to decide whether pii=maybe should be scrubbed.
     pub fn is_specific(&self) -> bool {
         match *self {
             SelectorSpec::And(ref selectors) => selectors.iter().any(SelectorSpec::is_specific),
             SelectorSpec::Or(ref selectors) => selectors.iter().all(SelectorSpec::is_specific),
-            SelectorSpec::Not(_) => false,
+            SelectorSpec::Not(ref selector) => match **selector {
+                // a double negation is equivalent to the inner selector
+                SelectorSpec::Not(ref inner) => inner.is_specific(),
+                _ => false,
+            },
+            SelectorSpec::IndexInsensitive(ref selector) => selector.is_specific(),
+            // a value can appear anywhere in the event
+            SelectorSpec::ValueMatches(_) => false,
             SelectorSpec::Path(ref path) => {
                 path.iter().enumerate().all(|(i, item)| {
                     match *item {
-                        SelectorPathItem::Type(ty) => match ty {
-                            // Basic value types cannot be part of a specific path
-                            ValueType::String
-                            | ValueType::Binary
-                            | ValueType::Number
-                            | ValueType::Boolean
-                            | ValueType::DateTime
-                            | ValueType::Array
-                            | ValueType::Object => false,
-
-                            // Other schema-specific value types can be if they are on the first
-                            // position. This list is explicitly typed out such that the decision
-                            // to add new value types to this list has to be made consciously.
-                            //
-                            // It's easy to change a `false` to `true` later, but a breaking change
-                            // to go the other direction. If you're not sure, return `false` for
-                            // your new value type.
-                            ValueType::Event
-                            | ValueType::Attachments
-                            | ValueType::Exception
-                            | ValueType::Stacktrace
-                            | ValueType::Frame
-                            | ValueType::Request
-                            | ValueType::User
-                            | ValueType::LogEntry
-                            | ValueType::Message
-                            | ValueType::Thread
-                            | ValueType::Breadcrumb
-                            | ValueType::Span
-                            | ValueType::Minidump
-                            | ValueType::HeapMemory
-                            | ValueType::StackMemory
-                            | ValueType::ClientSdkInfo => i == 0,
-                        },
+                        SelectorPathItem::Type(ty) => is_specific_type(ty, i),
+                        // a set is only as specific as its least specific member
+                        SelectorPathItem::TypeSet(ref types) => {
+                            types.iter().all(|&ty| is_specific_type(ty, i))
+                        }
+                        // also matches basic value types, which are never specific
+                        SelectorPathItem::AnyType => false,
+                        // anchors the path at the top-level value
+                        SelectorPathItem::Root => i == 0,
                         SelectorPathItem::Index(_) => true,
                         SelectorPathItem::Key(_) => true,
                         // necessary because of array indices
                         SelectorPathItem::Wildcard => true,
                         // a deep wildcard is too sweeping to be specific
                         SelectorPathItem::DeepWildcard => false,
+                        SelectorPathItem::DeepWildcardBounded(_) => false,
                     }
                 })
             }
@@ -149,6 +405,44 @@ impl SelectorSpec {
     }
 }
 
+/// Returns whether a type selector at position `i` of a path can be part of a specific selector.
+fn is_specific_type(ty: ValueType, i: usize) -> bool {
+    match ty {
+        // Basic value types cannot be part of a specific path
+        ValueType::String
+        | ValueType::Binary
+        | ValueType::Number
+        | ValueType::Boolean
+        | ValueType::DateTime
+        | ValueType::Array
+        | ValueType::Object => false,
+
+        // Other schema-specific value types can be if they are on the first
+        // position. This list is explicitly typed out such that the decision
+        // to add new value types to this list has to be made consciously.
+        //
+        // It's easy to change a `false` to `true` later, but a breaking change
+        // to go the other direction. If you're not sure, return `false` for
+        // your new value type.
+        ValueType::Event
+        | ValueType::Attachments
+        | ValueType::Exception
+        | ValueType::Stacktrace
+        | ValueType::Frame
+        | ValueType::Request
+        | ValueType::User
+        | ValueType::LogEntry
+        | ValueType::Message
+        | ValueType::Thread
+        | ValueType::Breadcrumb
+        | ValueType::Span
+        | ValueType::Minidump
+        | ValueType::HeapMemory
+        | ValueType::StackMemory
+        | ValueType::ClientSdkInfo => i == 0,
+    }
+}
+
 impl fmt::Display for SelectorSpec {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -163,6 +457,8 @@ impl fmt::Display for SelectorSpec {
                         SelectorSpec::Or(_) => true,
                         SelectorSpec::Not(_) => false,
                         SelectorSpec::Path(_) => false,
+                        SelectorSpec::IndexInsensitive(_) => false,
+                        SelectorSpec::ValueMatches(_) => false,
                     };
 
                     if needs_parens {
@@ -190,6 +486,8 @@ impl fmt::Display for SelectorSpec {
                     SelectorSpec::Or(_) => true,
                     SelectorSpec::Not(_) => true,
                     SelectorSpec::Path(_) => false,
+                    SelectorSpec::IndexInsensitive(_) => false,
+                    SelectorSpec::ValueMatches(_) => false,
                 };
 
                 if needs_parens {
@@ -206,6 +504,8 @@ impl fmt::Display for SelectorSpec {
                     write!(f, "{}", item)?;
                 }
             }
+            SelectorSpec::IndexInsensitive(ref x) => write!(f, "$noindex({})", x)?,
+            SelectorSpec::ValueMatches(ref predicate) => write!(f, "{}", predicate)?,
         }
         Ok(())
     }
@@ -215,31 +515,36 @@ impl FromStr for SelectorSpec {
     type Err = InvalidSelectorError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // these are temporary legacy selectors
-        match s {
-            "freeform" | "email" | "sensitive" | "text" => {
-                return Ok(SelectorSpec::Path(vec![SelectorPathItem::Type(
-                    ValueType::String,
-                )]));
-            }
-            "databag" | "container" => {
-                return Ok(SelectorSpec::Path(vec![SelectorPathItem::Type(
-                    ValueType::Object,
-                )]));
-            }
-            _ => {}
-        }
+        SelectorSpec::parse_with_limit(s, DEFAULT_MAX_SELECTOR_PATH_LEN)
+    }
+}
 
-        handle_selector(
-            SelectorParser::parse(Rule::RootSelector, s)
-                .map_err(InvalidSelectorError::ParseError)?
-                .next()
-                .unwrap()
-                .into_inner()
-                .next()
-                .unwrap(),
-        )
+/// Parses a selector without checking its length.
+fn parse_selector(s: &str) -> Result<SelectorSpec, InvalidSelectorError> {
+    // these are temporary legacy selectors
+    match s {
+        "freeform" | "email" | "sensitive" | "text" => {
+            return Ok(SelectorSpec::Path(vec![SelectorPathItem::Type(
+                ValueType::String,
+            )]));
+        }
+        "databag" | "container" => {
+            return Ok(SelectorSpec::Path(vec![SelectorPathItem::Type(
+                ValueType::Object,
+            )]));
+        }
+        _ => {}
     }
+
+    handle_selector(
+        SelectorParser::parse(Rule::RootSelector, s)
+            .map_err(InvalidSelectorError::ParseError)?
+            .next()
+            .unwrap()
+            .into_inner()
+            .next()
+            .unwrap(),
+    )
 }
 
 impl_str_serde!(SelectorSpec);
@@ -281,7 +586,7 @@ fn handle_selector(pair: Pair<Rule>) -> Result<SelectorSpec, InvalidSelectorErro
                 .into_inner()
                 .map(|item| {
                     let rv = handle_selector_path_item(item)?;
-                    if rv == SelectorPathItem::DeepWildcard {
+                    if rv.is_deep_wildcard() {
                         if used_deep_wildcard {
                             return Err(InvalidSelectorError::InvalidDeepWildcard);
                         } else {
@@ -296,8 +601,25 @@ fn handle_selector(pair: Pair<Rule>) -> Result<SelectorSpec, InvalidSelectorErro
                 return Err(InvalidSelectorError::InvalidWildcard);
             }
 
+            if items
+                .iter()
+                .skip(1)
+                .any(|item| *item == SelectorPathItem::Root)
+            {
+                return Err(InvalidSelectorError::InvalidRoot);
+            }
+
             Ok(SelectorSpec::Path(items))
         }
+        Rule::ValueMatches => {
+            let pattern = handle_quoted(pair.into_inner().next().unwrap());
+            let predicate =
+                ValuePredicate::new(&pattern).map_err(InvalidSelectorError::InvalidPattern)?;
+            Ok(SelectorSpec::ValueMatches(predicate))
+        }
+        Rule::IndexInsensitive => Ok(SelectorSpec::IndexInsensitive(Box::new(handle_selector(
+            pair.into_inner().next().unwrap(),
+        )?))),
         Rule::AndSelector => map_multiple_or_inner(pair, SelectorSpec::And),
         Rule::OrSelector => map_multiple_or_inner(pair, SelectorSpec::Or),
         Rule::NotSelector => Ok(SelectorSpec::Not(Box::new(handle_selector(
@@ -313,6 +635,17 @@ fn handle_selector(pair: Pair<Rule>) -> Result<SelectorSpec, InvalidSelectorErro
 fn handle_selector_path_item(pair: Pair<Rule>) -> Result<SelectorPathItem, InvalidSelectorError> {
     let pair = pair.into_inner().next().unwrap();
     match pair.as_rule() {
+        Rule::AnyType => Ok(SelectorPathItem::AnyType),
+        Rule::Root => Ok(SelectorPathItem::Root),
+        Rule::TypeSet => Ok(SelectorPathItem::TypeSet(
+            pair.into_inner()
+                .map(|ty| {
+                    ty.as_str()
+                        .parse()
+                        .map_err(|_| InvalidSelectorError::UnknownType)
+                })
+                .collect::<Result<_, _>>()?,
+        )),
         Rule::ObjectType => Ok(SelectorPathItem::Type(
             pair.as_str()[1..]
                 .parse()
@@ -320,6 +653,22 @@ fn handle_selector_path_item(pair: Pair<Rule>) -> Result<SelectorPathItem, Inval
         )),
         Rule::Wildcard => Ok(SelectorPathItem::Wildcard),
         Rule::DeepWildcard => Ok(SelectorPathItem::DeepWildcard),
+        Rule::DeepWildcardBounded => {
+            let max_levels = pair
+                .into_inner()
+                .next()
+                .unwrap()
+                .as_str()
+                .parse()
+                .map_err(|_| InvalidSelectorError::InvalidIndex)?;
+
+            // deep wildcards always span at least one level
+            if max_levels == 0 {
+                return Err(InvalidSelectorError::InvalidIndex);
+            }
+
+            Ok(SelectorPathItem::DeepWildcardBounded(max_levels))
+        }
         Rule::Index => Ok(SelectorPathItem::Index(
             pair.as_str()
                 .parse()
@@ -337,13 +686,7 @@ fn handle_key(pair: Pair<Rule>) -> Result<String, InvalidSelectorError> {
     let pair = pair.into_inner().next().unwrap();
     match pair.as_rule() {
         Rule::UnquotedKey => Ok(pair.as_str().to_owned()),
-        Rule::QuotedKey => Ok({
-            let mut key = String::new();
-            for token in pair.into_inner() {
-                key.push_str(token.as_str());
-            }
-            key
-        }),
+        Rule::QuotedKey => Ok(handle_quoted(pair)),
         rule => Err(InvalidSelectorError::UnexpectedToken(
             format!("{:?}", rule),
             "a key",
@@ -351,6 +694,15 @@ fn handle_key(pair: Pair<Rule>) -> Result<String, InvalidSelectorError> {
     }
 }
 
+/// Unescapes the contents of a quoted string.
+fn handle_quoted(pair: Pair<Rule>) -> String {
+    let mut string = String::new();
+    for token in pair.into_inner() {
+        string.push_str(token.as_str());
+    }
+    string
+}
+
 fn key_needs_quoting(key: &str) -> bool {
     SelectorParser::parse(Rule::RootUnquotedKey, key).is_err()
 }
@@ -366,6 +718,37 @@ fn test_roundtrip() {
     check_roundtrip("!a && !b");
     check_roundtrip("!(a && !b)");
     check_roundtrip("!(a && b)");
+    check_roundtrip("$*.foo");
+    check_roundtrip("$(error|thread).foo");
+    check_roundtrip("$.user.id");
+    check_roundtrip("user.**{3}.id");
+    check_roundtrip("$value('^[^@]+@[^@]+$') && !user.id");
+    check_roundtrip("$value('it''s')");
+    check_roundtrip("$noindex(extra.foo)");
+    check_roundtrip("!$noindex($user.** && !extra.bar) || a");
+
+    // canonical form with normalized operators and minimal parentheses
+    let selector = SelectorSpec::from_str("(** | user.*) & !(foo.bar.baz)").unwrap();
+    let canonical = selector.to_string();
+    assert_eq!(canonical, "(** || user.*) && !foo.bar.baz");
+    assert_eq!(SelectorSpec::from_str(&canonical).unwrap(), selector);
+}
+
+#[test]
+fn test_operator_aliases() {
+    fn parse(s: &str) -> SelectorSpec {
+        SelectorSpec::from_str(s).unwrap()
+    }
+
+    assert_eq!(parse("a & b"), parse("a && b"));
+    assert_eq!(parse("a | b"), parse("a || b"));
+    assert_eq!(parse("~a"), parse("!a"));
+    assert_eq!(
+        parse("(**.0 | absolutebogus)"),
+        parse("(**.0 || absolutebogus)")
+    );
+    assert_eq!(parse("a & b | ~c"), parse("a && b || !c"));
+    assert_eq!(parse("a|b&c").to_string(), "a || b && c");
 }
 
 #[test]
@@ -378,6 +761,21 @@ fn test_is_specific() {
         .is_specific());
     assert!(!SelectorSpec::from_str("$object.foo").unwrap().is_specific());
     assert!(SelectorSpec::from_str("extra.foo").unwrap().is_specific());
+    assert!(SelectorSpec::from_str("user.extra").unwrap().is_specific());
+    assert!(SelectorSpec::from_str("user.*").unwrap().is_specific());
+    assert!(!SelectorSpec::from_str("$user.**").unwrap().is_specific());
+    assert!(!SelectorSpec::from_str("(** || user.*)")
+        .unwrap()
+        .is_specific());
+    assert!(!SelectorSpec::from_str("$*").unwrap().is_specific());
+    assert!(!SelectorSpec::from_str("$*.foo").unwrap().is_specific());
+    assert!(!SelectorSpec::from_str("foo.$*").unwrap().is_specific());
+    assert!(SelectorSpec::from_str("$(exception|thread).foo")
+        .unwrap()
+        .is_specific());
+    assert!(!SelectorSpec::from_str("$(exception|string).foo")
+        .unwrap()
+        .is_specific());
 
     assert!(SelectorSpec::from_str("extra.foo && extra.foo")
         .unwrap()
@@ -398,6 +796,90 @@ fn test_is_specific() {
     assert!(!SelectorSpec::from_str("$string || $string")
         .unwrap()
         .is_specific());
+
+    assert!(!SelectorSpec::from_str("!extra.foo").unwrap().is_specific());
+    assert!(SelectorSpec::from_str("!(!extra.foo)")
+        .unwrap()
+        .is_specific());
+    assert!(!SelectorSpec::from_str("!(!$string)").unwrap().is_specific());
+    assert!(!SelectorSpec::from_str("!(!(!extra.foo))")
+        .unwrap()
+        .is_specific());
+}
+
+#[test]
+fn test_lint() {
+    assert_eq!(
+        SelectorSpec::from_str("extra.foo && !extra.foo")
+            .unwrap()
+            .lint(),
+        vec![SelectorLintWarning::Contradiction(
+            SelectorSpec::from_str("extra.foo").unwrap()
+        )]
+    );
+    assert_eq!(
+        SelectorSpec::from_str("$user.** || extra.foo")
+            .unwrap()
+            .lint(),
+        vec![SelectorLintWarning::NonSpecificDeepWildcard]
+    );
+    assert_eq!(
+        SelectorSpec::Or(vec![]).lint(),
+        vec![SelectorLintWarning::EmptyOr]
+    );
+
+    assert!(SelectorSpec::from_str("extra.foo && !extra.bar")
+        .unwrap()
+        .lint()
+        .is_empty());
+    assert!(SelectorSpec::from_str("$frame.vars.** && $frame.vars.foo")
+        .unwrap()
+        .lint()
+        .is_empty());
+}
+
+#[test]
+fn test_quoted_key_with_dots() {
+    let extra_state = ProcessingState::root().enter_static("extra", None, None); // .extra
+    let flag_state = extra_state.enter_static("com.example.flag", None, None); // .extra.'com.example.flag'
+    let com_state = extra_state.enter_static("com", None, None); // .extra.com
+
+    let selector = SelectorSpec::from_str("extra.'com.example.flag'").unwrap();
+    assert_eq!(
+        selector,
+        SelectorSpec::Path(vec![
+            SelectorPathItem::Key("extra".to_owned()),
+            SelectorPathItem::Key("com.example.flag".to_owned()),
+        ])
+    );
+    assert_eq!(selector.to_string(), "extra.'com.example.flag'");
+    assert!(flag_state.path().matches_selector(&selector));
+    assert!(!com_state.path().matches_selector(&selector));
+
+    // quotes are escaped by doubling them
+    let selector = SelectorSpec::from_str("extra.'it''s.here'").unwrap();
+    let quote_state = extra_state.enter_static("it's.here", None, None);
+    assert!(quote_state.path().matches_selector(&selector));
+    assert!(!flag_state.path().matches_selector(&selector));
+}
+
+#[test]
+fn test_max_path_len() {
+    let selector = SelectorSpec::from_str("$user.extra.foo || !a.b.c.d").unwrap();
+    assert_eq!(selector.max_path_len(), 4);
+
+    let deep = vec!["a"; DEFAULT_MAX_SELECTOR_PATH_LEN + 1].join(".");
+    assert!(matches!(
+        SelectorSpec::from_str(&deep),
+        Err(InvalidSelectorError::PathTooLong(
+            DEFAULT_MAX_SELECTOR_PATH_LEN
+        ))
+    ));
+    assert!(SelectorSpec::parse_with_limit(&deep, DEFAULT_MAX_SELECTOR_PATH_LEN + 1).is_ok());
+    assert!(matches!(
+        SelectorSpec::parse_with_limit("a.b.c", 2),
+        Err(InvalidSelectorError::PathTooLong(2))
+    ));
 }
 
 #[test]
@@ -410,4 +892,97 @@ fn test_invalid() {
         SelectorSpec::from_str("$frame.**.foo.**"),
         Err(InvalidSelectorError::InvalidDeepWildcard)
     ));
+    assert!(matches!(
+        SelectorSpec::from_str("user.$"),
+        Err(InvalidSelectorError::InvalidRoot)
+    ));
+    assert!(matches!(
+        SelectorSpec::from_str("user.**{2}.**"),
+        Err(InvalidSelectorError::InvalidDeepWildcard)
+    ));
+    assert!(matches!(
+        SelectorSpec::from_str("user.**{0}"),
+        Err(InvalidSelectorError::InvalidIndex)
+    ));
+}
+
+#[test]
+fn test_matches_path() {
+    let segments = [
+        PathSegment::Key("user".to_owned()),
+        PathSegment::Key("extra".to_owned()),
+        PathSegment::Key("foo".to_owned()),
+        PathSegment::Index(0),
+    ];
+    let root_type = Some(ValueType::Event);
+    let types = [
+        Some(ValueType::User),
+        Some(ValueType::Object),
+        Some(ValueType::Array),
+        None,
+    ];
+
+    let event_state = ProcessingState::new_root(None, Some(ValueType::Event)); // .
+    let user_state = event_state.enter_static("user", None, Some(ValueType::User)); // .user
+    let extra_state = user_state.enter_static("extra", None, Some(ValueType::Object)); // .user.extra
+    let foo_state = extra_state.enter_static("foo", None, Some(ValueType::Array)); // .user.extra.foo
+    let zero_state = foo_state.enter_index(0, None, None); // .user.extra.foo.0
+
+    for selector in &[
+        "$user.extra.foo.*",
+        "$user.extra.foo.0",
+        "$user.extra.foo.1",
+        "$user.extra.bar.**",
+        "$user.**.0",
+        "$event.**",
+        "$user.$object.**.0",
+        "$array.*",
+        "$*",
+        "extra.foo.0 && !extra.foo.1",
+    ] {
+        let selector: SelectorSpec = selector.parse().unwrap();
+        assert_eq!(
+            selector.matches_path(&segments, root_type, &types, Pii::True),
+            zero_state.path().matches_selector(&selector),
+            "{}",
+            selector
+        );
+        assert_eq!(
+            selector.matches_path(&segments[..3], root_type, &types[..3], Pii::True),
+            foo_state.path().matches_selector(&selector),
+            "{}",
+            selector
+        );
+    }
+
+    let selector: SelectorSpec = "$user.extra.foo.0".parse().unwrap();
+    assert!(selector.matches_path(&segments, root_type, &types, Pii::Maybe));
+    assert!(!selector.matches_path(&segments, root_type, &types, Pii::False));
+
+    let selector: SelectorSpec = "$user.**".parse().unwrap();
+    assert!(selector.matches_path(&segments, root_type, &types, Pii::True));
+    assert!(!selector.matches_path(&segments, root_type, &types, Pii::Maybe));
+}
+
+#[test]
+fn test_exact_for_path() {
+    let event_state = ProcessingState::new_root(None, Some(ValueType::Event)); // .
+    let user_state = event_state.enter_static("user", None, Some(ValueType::User)); // .user
+    let extra_state = user_state.enter_static("extra", None, Some(ValueType::Object)); // .user.extra
+    let foo_state = extra_state.enter_static("foo", None, Some(ValueType::Array)); // .user.extra.foo
+    let zero_state = foo_state.enter_index(0, None, None); // .user.extra.foo.0
+
+    let selector = SelectorSpec::exact_for_path(&foo_state.path()).unwrap();
+    assert_eq!(selector.to_string(), "user.extra.foo");
+    assert!(selector.is_specific());
+    assert!(foo_state.path().matches_selector(&selector));
+    assert!(!extra_state.path().matches_selector(&selector));
+    assert!(!zero_state.path().matches_selector(&selector));
+
+    let selector = SelectorSpec::exact_for_path(&zero_state.path()).unwrap();
+    assert_eq!(selector.to_string(), "user.extra.foo.0");
+    assert!(zero_state.path().matches_selector(&selector));
+    assert!(!foo_state.path().matches_selector(&selector));
+
+    assert_eq!(SelectorSpec::exact_for_path(&event_state.path()), None);
 }
@@ -48,9 +48,13 @@ pub use self::mechanism::{CError, MachException, Mechanism, MechanismMeta, Posix
 pub use self::metrics::Metrics;
 pub use self::request::{Cookies, HeaderName, HeaderValue, Headers, Query, Request};
 #[cfg(feature = "jsonschema")]
-pub use self::schema::event_json_schema;
+pub use self::schema::{event_json_schema, session_json_schema};
 pub use self::security_report::{Csp, ExpectCt, ExpectStaple, Hpkp, SecurityReportType};
-pub use self::session::{ParseSessionStatusError, SessionAttributes, SessionStatus, SessionUpdate};
+pub use self::session::{
+    dedup_sessions, AnonymizeOptions, ClientSdkInfoLite, ParseSessionStatusError, SessionAggregate,
+    SessionAttributes, SessionCounters, SessionCounts, SessionGapDetector, SessionParseError,
+    SessionStatus, SessionTimeError, SessionUpdate, DEFAULT_MAX_SESSION_ERRORS,
+};
 pub use self::span::Span;
 pub use self::stacktrace::{Frame, FrameData, FrameVars, RawStacktrace, Stacktrace};
 pub use self::tags::{TagEntry, Tags};
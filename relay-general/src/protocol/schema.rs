@@ -1,6 +1,11 @@
-use crate::protocol::Event;
+use crate::protocol::{Event, SessionUpdate};
 
 /// Get the event schema as JSON schema. The return type is serde-serializable.
 pub fn event_json_schema() -> impl serde::Serialize {
     schemars::schema_for!(Event)
 }
+
+/// Get the session update schema as JSON schema. The return type is serde-serializable.
+pub fn session_json_schema() -> impl serde::Serialize {
+    schemars::schema_for!(SessionUpdate)
+}
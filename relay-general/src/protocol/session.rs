@@ -1,13 +1,22 @@
-use std::net::IpAddr;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::io::BufRead;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::SystemTime;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use failure::Fail;
-use serde::{Deserialize, Serialize};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::error::Category;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 /// The type of session event we're dealing with.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum SessionStatus {
     /// The session is healthy.
@@ -22,6 +31,47 @@ pub enum SessionStatus {
     Abnormal,
 }
 
+impl SessionStatus {
+    /// Returns `true` if the session has ended and will not receive further updates.
+    ///
+    /// `Ok` is the only non-terminal status.
+    pub fn is_terminal(self) -> bool {
+        match self {
+            SessionStatus::Ok => false,
+            SessionStatus::Exited | SessionStatus::Crashed | SessionStatus::Abnormal => true,
+        }
+    }
+
+    /// Returns `true` if a session in this status may report `next` in a later update.
+    ///
+    /// Healthy sessions can move to any status, while terminal statuses may only be repeated.
+    pub fn can_transition_to(self, next: SessionStatus) -> bool {
+        !self.is_terminal() || self == next
+    }
+
+    /// Returns the severity of this status, where a higher value is worse.
+    ///
+    /// The order is `ok < exited < abnormal < crashed`. Use this instead of the derived `Ord`,
+    /// which follows declaration order.
+    pub fn severity(self) -> u8 {
+        match self {
+            SessionStatus::Ok => 0,
+            SessionStatus::Exited => 1,
+            SessionStatus::Abnormal => 2,
+            SessionStatus::Crashed => 3,
+        }
+    }
+
+    /// Returns the more severe of two statuses.
+    pub fn max_severity(a: Self, b: Self) -> Self {
+        if b.severity() > a.severity() {
+            b
+        } else {
+            a
+        }
+    }
+}
+
 impl Default for SessionStatus {
     fn default() -> Self {
         Self::Ok
@@ -40,8 +90,43 @@ derive_fromstr_and_display!(SessionStatus, ParseSessionStatusError, {
     SessionStatus::Exited => "exited",
 });
 
+/// An error returned by `SessionUpdate::parse`.
+#[derive(Debug, Fail)]
+pub enum SessionParseError {
+    /// The payload is not valid JSON.
+    #[fail(display = "invalid session json")]
+    Json(#[cause] serde_json::Error),
+
+    /// A required field is missing from the payload.
+    #[fail(display = "missing session field {}", _0)]
+    MissingField(&'static str),
+
+    /// A field has an invalid type or value.
+    #[fail(display = "invalid session value")]
+    InvalidValue(#[cause] serde_json::Error),
+}
+
+impl From<serde_json::Error> for SessionParseError {
+    fn from(error: serde_json::Error) -> Self {
+        SessionParseError::Json(error)
+    }
+}
+
+/// An error returned by `SessionUpdate::validate_times`.
+#[derive(Debug, Fail, Eq, PartialEq)]
+pub enum SessionTimeError {
+    /// The update's timestamp is before the start of the session.
+    #[fail(display = "session timestamp is before the session started")]
+    TimestampBeforeStart,
+
+    /// The session started or was updated too far in the future.
+    #[fail(display = "session timestamp is in the future")]
+    InFuture,
+}
+
 /// Additional attributes for Sessions.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
 pub struct SessionAttributes {
     /// The release version string.
     pub release: String,
@@ -59,11 +144,72 @@ pub struct SessionAttributes {
     pub user_agent: Option<String>,
 }
 
+impl SessionAttributes {
+    /// Checks whether the session's environment is in the given allowlist.
+    ///
+    /// Environments are compared exactly, including case. Sessions without an environment are
+    /// allowed if `allow_missing` is set.
+    pub fn environment_allowed(&self, allowlist: &[String], allow_missing: bool) -> bool {
+        match self.environment {
+            Some(ref environment) => allowlist.iter().any(|allowed| allowed == environment),
+            None => allow_missing,
+        }
+    }
+
+    /// Returns the session's environment, or `default` if the session has none.
+    pub fn environment_or_default<'a>(&'a self, default: &'a str) -> &'a str {
+        self.environment.as_deref().unwrap_or(default)
+    }
+
+    /// Masks the host part of the ip address, if there is one.
+    ///
+    /// This zeroes the last octet of IPv4 addresses and the last 80 bits of IPv6 addresses.
+    pub fn anonymize_ip(&mut self) {
+        self.ip_address = self.ip_address.map(|ip_address| match ip_address {
+            IpAddr::V4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+            }
+            IpAddr::V6(ip) => {
+                let [a, b, c, ..] = ip.segments();
+                IpAddr::V6(Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0))
+            }
+        });
+    }
+}
+
+/// The default upper bound for the error count of a session, see `SessionUpdate::validate`.
+pub const DEFAULT_MAX_SESSION_ERRORS: u64 = 1_000_000;
+
+/// Returns the current time in milliseconds as sequence number.
+///
+/// If the system clock is before the UNIX epoch, this falls back to a counter that starts at `1`,
+/// so that the default sequence is never `0`.
 fn default_sequence() -> u64 {
-    SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+    static FALLBACK_SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => u64::try_from(duration.as_millis()).unwrap_or(u64::max_value()),
+        Err(_) => FALLBACK_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+    }
+}
+
+/// The name and version of the SDK that sent a session.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
+pub struct ClientSdkInfoLite {
+    /// The unique SDK name, such as `sentry.javascript.browser`.
+    pub name: String,
+    /// The semantic version of the SDK.
+    pub version: String,
+}
+
+/// Omits a default from the JSON schema, since it is generated anew for every value.
+///
+/// Otherwise, every call to `session_json_schema` would return a different schema.
+#[cfg(feature = "jsonschema")]
+fn skip_schema_default<T>(_: &T) -> bool {
+    true
 }
 
 #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -71,22 +217,82 @@ fn is_false(val: &bool) -> bool {
     !val
 }
 
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_ok_status(status: &SessionStatus) -> bool {
+    *status == SessionStatus::Ok
+}
+
+/// Deserializes a field that is present, even if its value is `null`.
+///
+/// Used together with `#[serde(default)]`, this yields `None` only for absent fields.
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Returns the first required field that is missing from a session payload.
+///
+/// This is only called after parsing failed, so that valid payloads are not parsed twice. Values
+/// of the required fields are skipped without being parsed. Fields set to `null` are present.
+fn missing_session_field(payload: &[u8]) -> Option<&'static str> {
+    #[derive(Deserialize)]
+    struct RequiredAttributes {
+        #[serde(default, deserialize_with = "deserialize_present")]
+        release: Option<IgnoredAny>,
+    }
+
+    #[derive(Deserialize)]
+    struct RequiredFields {
+        #[serde(default, deserialize_with = "deserialize_present")]
+        started: Option<IgnoredAny>,
+        #[serde(default, deserialize_with = "deserialize_present")]
+        attrs: Option<Option<RequiredAttributes>>,
+    }
+
+    let fields: RequiredFields = serde_json::from_slice(payload).ok()?;
+    match fields {
+        RequiredFields { started: None, .. } => Some("started"),
+        RequiredFields { attrs: None, .. } => Some("attrs"),
+        RequiredFields {
+            attrs: Some(Some(RequiredAttributes { release: None })),
+            ..
+        } => Some("attrs.release"),
+        _ => None,
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
 pub struct SessionUpdate {
     /// The session identifier.
     #[serde(rename = "sid", default = "Uuid::new_v4")]
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(skip_serializing_if = "skip_schema_default")
+    )]
     pub session_id: Uuid,
     /// The distinct identifier.
     #[serde(rename = "did", default)]
     pub distinct_id: Option<String>,
     /// An optional logical clock.
     #[serde(rename = "seq", default = "default_sequence")]
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(skip_serializing_if = "skip_schema_default")
+    )]
     pub sequence: u64,
     /// A flag that indicates that this is the initial transmission of the session.
     #[serde(default, skip_serializing_if = "is_false")]
     pub init: bool,
     /// The timestamp of when the session change event was created.
     #[serde(default = "Utc::now")]
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(skip_serializing_if = "skip_schema_default")
+    )]
     pub timestamp: DateTime<Utc>,
     /// The timestamp of when the session itself started.
     pub started: DateTime<Utc>,
@@ -94,7 +300,7 @@ pub struct SessionUpdate {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub duration: Option<f64>,
     /// The status of the session.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_ok_status")]
     pub status: SessionStatus,
     /// The number of errors that ocurred.
     #[serde(default)]
@@ -102,22 +308,435 @@ pub struct SessionUpdate {
     /// The session event attributes.
     #[serde(rename = "attrs")]
     pub attributes: SessionAttributes,
+    /// Information about the SDK that sent the session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk: Option<ClientSdkInfoLite>,
 }
 
 impl SessionUpdate {
     /// Parses a session update from JSON.
-    pub fn parse(payload: &[u8]) -> Result<Self, serde_json::Error> {
-        serde_json::from_slice(payload)
+    pub fn parse(payload: &[u8]) -> Result<Self, SessionParseError> {
+        serde_json::from_slice(payload).map_err(|error| match error.classify() {
+            Category::Data => match missing_session_field(payload) {
+                Some(field) => SessionParseError::MissingField(field),
+                None => SessionParseError::InvalidValue(error),
+            },
+            _ => SessionParseError::Json(error),
+        })
+    }
+
+    /// Parses session updates from newline-delimited JSON.
+    ///
+    /// Lines are read and parsed lazily, and blank lines are skipped. A malformed line yields an
+    /// error without ending the iterator.
+    pub fn parse_ndjson<R>(reader: R) -> impl Iterator<Item = Result<Self, SessionParseError>>
+    where
+        R: BufRead,
+    {
+        reader.lines().filter_map(|line| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(Self::parse(line.as_bytes())),
+            Err(error) => Some(Err(serde_json::Error::io(error).into())),
+        })
+    }
+
+    /// Sanitizes values reported by untrusted clients.
+    ///
+    /// This caps the error count at `max_errors`, which protects sums over many sessions from
+    /// overflowing. Returns `true` if the session was modified.
+    pub fn validate(&mut self, max_errors: u64) -> bool {
+        if self.errors > max_errors {
+            self.errors = max_errors;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Checks that the session's timestamps are plausible.
+    ///
+    /// This fails if the update's `timestamp` is before `started`, or if either of them is more
+    /// than an hour ahead of the current time. See `validate_times_with_skew` to configure this.
+    pub fn validate_times(&self) -> Result<(), SessionTimeError> {
+        self.validate_times_with_skew(Duration::hours(1))
+    }
+
+    /// Checks that the session's timestamps are plausible, allowing for the given clock skew.
+    pub fn validate_times_with_skew(&self, max_skew: Duration) -> Result<(), SessionTimeError> {
+        if self.timestamp < self.started {
+            return Err(SessionTimeError::TimestampBeforeStart);
+        }
+
+        // `started` is at most `timestamp`, as checked above.
+        if self.timestamp - Utc::now() > max_skew {
+            return Err(SessionTimeError::InFuture);
+        }
+
+        Ok(())
+    }
+
+    /// Collapses another update of the same session into this one.
+    ///
+    /// The result has the higher sequence, the more severe status, the sum of errors, the latest
+    /// timestamp, and the longer duration of both updates. It is an initial update if either of
+    /// them was. All other fields, such as the session identifier and attributes, are kept.
+    pub fn merge(&mut self, other: &SessionUpdate) {
+        self.sequence = self.sequence.max(other.sequence);
+        self.init |= other.init;
+        self.timestamp = self.timestamp.max(other.timestamp);
+        self.status = SessionStatus::max_severity(self.status, other.status);
+        self.errors = self.errors.saturating_add(other.errors);
+        self.duration = match (self.duration, other.duration) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
     }
 
     /// Serializes a session update back into JSON.
     pub fn serialize(&self) -> Result<Vec<u8>, serde_json::Error> {
         serde_json::to_vec(self)
     }
+
+    /// Returns the hex-encoded SHA-256 hash of the salted distinct identifier, if there is one.
+    ///
+    /// This allows counting unique users without storing their raw identifiers. The
+    /// `distinct_id` field itself is left untouched.
+    pub fn hashed_distinct_id(&self, salt: &[u8]) -> Option<String> {
+        let distinct_id = self.distinct_id.as_ref()?;
+        let mut hasher = Sha256::new();
+        hasher.input(salt);
+        hasher.input(distinct_id.as_bytes());
+        Some(format!("{:x}", hasher.result()))
+    }
+
+    /// Removes or masks identifying information from the session, as configured in `options`.
+    pub fn anonymize(&mut self, options: &AnonymizeOptions) {
+        self.distinct_id = match options.distinct_id_salt {
+            Some(ref salt) => self.hashed_distinct_id(salt),
+            None => None,
+        };
+
+        if options.anonymize_ip {
+            self.attributes.anonymize_ip();
+        }
+
+        if options.remove_user_agent {
+            self.attributes.user_agent = None;
+        }
+    }
+}
+
+/// Configures `SessionUpdate::anonymize`.
+#[derive(Clone, Debug)]
+pub struct AnonymizeOptions {
+    /// Replaces the distinct id with its salted hash, see `SessionUpdate::hashed_distinct_id`.
+    ///
+    /// If this is `None`, the distinct id is removed.
+    pub distinct_id_salt: Option<Vec<u8>>,
+    /// Masks the ip address, see `SessionAttributes::anonymize_ip`. Defaults to `true`.
+    pub anonymize_ip: bool,
+    /// Removes the user agent. Defaults to `false`.
+    pub remove_user_agent: bool,
+}
+
+impl Default for AnonymizeOptions {
+    fn default() -> Self {
+        AnonymizeOptions {
+            distinct_id_salt: None,
+            anonymize_ip: true,
+            remove_user_agent: false,
+        }
+    }
+}
+
+/// A map that forgets its least recently used entries beyond a fixed capacity.
+///
+/// Both reading an entry through `get_mut` and inserting it count as a use. With a capacity of
+/// zero, nothing is stored.
+#[derive(Clone, Debug)]
+struct LruMap<K, V> {
+    capacity: usize,
+    clock: u64,
+    entries: HashMap<K, (V, u64)>,
+    recency: BTreeMap<u64, K>,
+}
+
+impl<K, V> LruMap<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    fn new(capacity: usize) -> Self {
+        LruMap {
+            capacity,
+            clock: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the value for `key` and marks it as recently used.
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.clock += 1;
+        self.recency.remove(last_used);
+        self.recency.insert(self.clock, key.clone());
+        *last_used = self.clock;
+        Some(value)
+    }
+
+    /// Inserts or replaces the value for `key`, evicting the least recently used entry if the map
+    /// is full.
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.clock += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.clock)) {
+            self.recency.remove(&last_used);
+        } else if self.entries.len() > self.capacity {
+            let oldest = self.recency.keys().next().copied();
+            if let Some(oldest_key) = oldest.and_then(|tick| self.recency.remove(&tick)) {
+                self.entries.remove(&oldest_key);
+            }
+        }
+        self.recency.insert(self.clock, key);
+    }
+}
+
+/// Drops session updates that have the same session id and sequence as a previous update.
+///
+/// This removes duplicates from retried transmissions. To bound memory, only the `capacity` most
+/// recently seen updates are remembered, where a dropped duplicate also counts as seen. Once this
+/// is exceeded, the least recently seen update is forgotten, and a repeated transmission of it
+/// passes again.
+pub fn dedup_sessions<I>(updates: I, capacity: usize) -> impl Iterator<Item = SessionUpdate>
+where
+    I: IntoIterator<Item = SessionUpdate>,
+{
+    let mut seen = LruMap::new(capacity);
+
+    updates.into_iter().filter(move |update| {
+        let key = (update.session_id, update.sequence);
+        if seen.get_mut(&key).is_some() {
+            return false;
+        }
+
+        seen.insert(key, ());
+        true
+    })
+}
+
+/// Detects lost updates by tracking the sequence numbers of sessions.
+///
+/// This only gives meaningful results for clients that send a contiguous `seq`. If the sequence
+/// is omitted, it defaults to a timestamp and every update looks like a gap.
+///
+/// To bound memory, at most `capacity` sessions are tracked. When a new session exceeds this, the
+/// session that has not been updated for the longest time is forgotten, and its next update is
+/// treated like a new session.
+#[derive(Clone, Debug)]
+pub struct SessionGapDetector {
+    sequences: LruMap<Uuid, u64>,
+}
+
+impl SessionGapDetector {
+    /// Creates a detector that tracks up to `capacity` sessions.
+    pub fn new(capacity: usize) -> Self {
+        SessionGapDetector {
+            sequences: LruMap::new(capacity),
+        }
+    }
+
+    /// Records an update and returns the number of updates missing before it, if any.
+    ///
+    /// The first update of a session never reports a gap. Repeated or out-of-order updates with a
+    /// lower sequence are ignored.
+    pub fn observe(&mut self, update: &SessionUpdate) -> Option<u64> {
+        if let Some(last) = self.sequences.get_mut(&update.session_id) {
+            let expected = last.saturating_add(1);
+            *last = (*last).max(update.sequence);
+            return match update.sequence.checked_sub(expected) {
+                Some(gap) if gap > 0 => Some(gap),
+                _ => None,
+            };
+        }
+
+        self.sequences.insert(update.session_id, update.sequence);
+        None
+    }
+}
+
+/// Session counts by status, as returned by `SessionCounters::snapshot`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SessionCounts {
+    /// The number of sessions that are healthy.
+    pub ok: u64,
+    /// The number of sessions that terminated normally.
+    pub exited: u64,
+    /// The number of sessions that resulted in an application crash.
+    pub crashed: u64,
+    /// The number of sessions that had an unexpected abrupt termination.
+    pub abnormal: u64,
+    /// The total number of errors across all sessions.
+    pub errors: u64,
+    /// The number of sessions that reported at least one error.
+    pub errored: u64,
+}
+
+impl SessionCounts {
+    /// Returns the number of sessions in the given status.
+    pub fn get(&self, status: SessionStatus) -> u64 {
+        match status {
+            SessionStatus::Ok => self.ok,
+            SessionStatus::Exited => self.exited,
+            SessionStatus::Crashed => self.crashed,
+            SessionStatus::Abnormal => self.abnormal,
+        }
+    }
+
+    /// Returns the total number of sessions.
+    pub fn total(&self) -> u64 {
+        self.ok + self.exited + self.crashed + self.abnormal
+    }
+}
+
+/// Running tallies of sessions by status, derived from a stream of session updates.
+///
+/// Every session is counted exactly once, under the status of its latest update. Subsequent updates
+/// for the same session move it to another status rather than counting it again, and updates with
+/// a lower sequence than the last recorded one are ignored. Since this keeps track of every session
+/// it has seen, it is meant for bounded streams such as health checks.
+#[derive(Clone, Debug, Default)]
+pub struct SessionCounters {
+    sessions: HashMap<Uuid, (u64, SessionStatus, u64)>,
+    counts: SessionCounts,
+}
+
+impl SessionCounters {
+    /// Creates empty session counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a session update.
+    pub fn record(&mut self, update: &SessionUpdate) {
+        let entry = (update.sequence, update.status, update.errors);
+        let previous = match self.sessions.get(&update.session_id) {
+            Some(existing) if existing.0 > update.sequence => return,
+            Some(&existing) => Some(existing),
+            None => None,
+        };
+
+        if let Some((_, status, errors)) = previous {
+            self.count(status, errors, false);
+        }
+
+        self.count(update.status, update.errors, true);
+        self.sessions.insert(update.session_id, entry);
+    }
+
+    /// Returns the current tallies.
+    pub fn snapshot(&self) -> SessionCounts {
+        self.counts
+    }
+
+    fn count(&mut self, status: SessionStatus, errors: u64, add: bool) {
+        let apply = |counter: &mut u64, amount: u64| {
+            if add {
+                *counter = counter.saturating_add(amount);
+            } else {
+                *counter = counter.saturating_sub(amount);
+            }
+        };
+
+        let counts = &mut self.counts;
+        match status {
+            SessionStatus::Ok => apply(&mut counts.ok, 1),
+            SessionStatus::Exited => apply(&mut counts.exited, 1),
+            SessionStatus::Crashed => apply(&mut counts.crashed, 1),
+            SessionStatus::Abnormal => apply(&mut counts.abnormal, 1),
+        }
+
+        apply(&mut counts.errors, errors);
+        if errors > 0 {
+            apply(&mut counts.errored, 1);
+        }
+    }
+}
+
+/// Release health totals over a stream of session updates.
+///
+/// This is based on `SessionCounters`, so it only counts the latest state of every session. A
+/// session transitioning from `ok` to `crashed` counts once as crashed. Updates with a lower
+/// sequence than the last ingested one for the same session are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct SessionAggregate {
+    counters: SessionCounters,
+}
+
+impl SessionAggregate {
+    /// Creates an empty aggregate.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an aggregate from all the given session updates.
+    pub fn from_updates_iter<'a, I>(updates: I) -> Self
+    where
+        I: IntoIterator<Item = &'a SessionUpdate>,
+    {
+        let mut aggregate = Self::new();
+        for update in updates {
+            aggregate.ingest(update);
+        }
+        aggregate
+    }
+
+    /// Adds a session update to the aggregate.
+    pub fn ingest(&mut self, update: &SessionUpdate) {
+        self.counters.record(update);
+    }
+
+    /// Returns the number of distinct sessions.
+    pub fn total(&self) -> u64 {
+        self.counters.snapshot().total()
+    }
+
+    /// Returns the number of sessions that resulted in an application crash.
+    pub fn crashed(&self) -> u64 {
+        self.counters.snapshot().crashed
+    }
+
+    /// Returns the number of sessions that had an unexpected abrupt termination.
+    pub fn abnormal(&self) -> u64 {
+        self.counters.snapshot().abnormal
+    }
+
+    /// Returns the number of sessions that reported at least one error.
+    pub fn errored(&self) -> u64 {
+        self.counters.snapshot().errored
+    }
+
+    /// Returns the ratio of sessions that did not crash, between `0.0` and `1.0`.
+    ///
+    /// An empty aggregate is considered entirely crash free.
+    pub fn crash_free_rate(&self) -> f64 {
+        let counts = self.counters.snapshot();
+        let total = counts.total();
+        if total == 0 {
+            return 1.0;
+        }
+
+        1.0 - counts.crashed as f64 / total as f64
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
     use super::*;
 
     #[test]
@@ -137,7 +756,6 @@ mod tests {
   "seq": 4711,
   "timestamp": "2020-02-07T15:17:00Z",
   "started": "2020-02-07T14:16:00Z",
-  "status": "ok",
   "errors": 0,
   "attrs": {
     "release": "sentry-test@1.0.0"
@@ -160,6 +778,7 @@ mod tests {
                 ip_address: None,
                 user_agent: None,
             },
+            sdk: None,
         };
 
         let mut parsed = SessionUpdate::parse(json.as_bytes()).unwrap();
@@ -172,6 +791,76 @@ mod tests {
         assert_eq_str!(output, serde_json::to_string_pretty(&update).unwrap());
     }
 
+    #[test]
+    fn test_session_status_ok_omitted() {
+        let json = r#"{
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let mut update = SessionUpdate::parse(json.as_bytes()).unwrap();
+        assert_eq!(update.status, SessionStatus::Ok);
+
+        let serialized = String::from_utf8(update.serialize().unwrap()).unwrap();
+        assert!(!serialized.contains("\"status\""));
+
+        update.status = SessionStatus::Crashed;
+        let serialized = String::from_utf8(update.serialize().unwrap()).unwrap();
+        assert!(serialized.contains("\"status\":\"crashed\""));
+    }
+
+    #[test]
+    fn test_session_parse_errors() {
+        let json = r#"{"attrs": {"release": "sentry-test@1.0.0"}}"#;
+        assert!(matches!(
+            SessionUpdate::parse(json.as_bytes()),
+            Err(SessionParseError::MissingField("started"))
+        ));
+
+        // fields set to null are present, but invalid
+        for json in &[
+            r#"{"started": null, "attrs": {"release": "sentry-test@1.0.0"}}"#,
+            r#"{"started": "2020-02-07T14:16:00Z", "attrs": null}"#,
+            r#"{"started": "2020-02-07T14:16:00Z", "attrs": {"release": null}}"#,
+        ] {
+            assert!(matches!(
+                SessionUpdate::parse(json.as_bytes()),
+                Err(SessionParseError::InvalidValue(_))
+            ));
+        }
+
+        let json = r#"{"started": "2020-02-07T14:16:00Z"}"#;
+        assert!(matches!(
+            SessionUpdate::parse(json.as_bytes()),
+            Err(SessionParseError::MissingField("attrs"))
+        ));
+
+        let json = r#"{"started": "2020-02-07T14:16:00Z", "attrs": {}}"#;
+        assert!(matches!(
+            SessionUpdate::parse(json.as_bytes()),
+            Err(SessionParseError::MissingField("attrs.release"))
+        ));
+
+        let json = r#"{
+  "started": "2020-02-07T14:16:00Z",
+  "status": "bogus",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+        assert!(matches!(
+            SessionUpdate::parse(json.as_bytes()),
+            Err(SessionParseError::InvalidValue(_))
+        ));
+
+        assert!(matches!(
+            SessionUpdate::parse(b"{"),
+            Err(SessionParseError::Json(_))
+        ));
+    }
+
     #[test]
     fn test_session_default_timestamp_and_sid() {
         let json = r#"{
@@ -221,9 +910,570 @@ mod tests {
                 ip_address: Some("::1".parse().unwrap()),
                 user_agent: Some("Firefox/72.0".to_owned()),
             },
+            sdk: None,
         };
 
         assert_eq_dbg!(update, SessionUpdate::parse(json.as_bytes()).unwrap());
         assert_eq_str!(json, serde_json::to_string_pretty(&update).unwrap());
     }
+
+    #[test]
+    fn test_session_sdk_roundtrip() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "did": null,
+  "seq": 42,
+  "timestamp": "2020-02-07T15:17:00Z",
+  "started": "2020-02-07T14:16:00Z",
+  "errors": 0,
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  },
+  "sdk": {
+    "name": "sentry.javascript.browser",
+    "version": "5.27.0"
+  }
+}"#;
+
+        let update = SessionUpdate::parse(json.as_bytes()).unwrap();
+        assert_eq!(
+            update.sdk,
+            Some(ClientSdkInfoLite {
+                name: "sentry.javascript.browser".to_owned(),
+                version: "5.27.0".to_owned(),
+            })
+        );
+        assert_eq_str!(json, serde_json::to_string_pretty(&update).unwrap());
+
+        let mut update = update;
+        update.sdk = None;
+        let serialized = serde_json::to_string(&update).unwrap();
+        assert!(!serialized.contains("sdk"));
+        assert_eq!(SessionUpdate::parse(serialized.as_bytes()).unwrap(), update);
+    }
+
+    #[test]
+    fn test_session_status_is_terminal() {
+        assert!(!SessionStatus::Ok.is_terminal());
+        assert!(SessionStatus::Exited.is_terminal());
+        assert!(SessionStatus::Crashed.is_terminal());
+        assert!(SessionStatus::Abnormal.is_terminal());
+    }
+
+    #[test]
+    fn test_session_status_transitions() {
+        assert!(SessionStatus::Ok.can_transition_to(SessionStatus::Ok));
+        assert!(SessionStatus::Ok.can_transition_to(SessionStatus::Crashed));
+        assert!(SessionStatus::Crashed.can_transition_to(SessionStatus::Crashed));
+        assert!(!SessionStatus::Crashed.can_transition_to(SessionStatus::Ok));
+        assert!(!SessionStatus::Exited.can_transition_to(SessionStatus::Abnormal));
+    }
+
+    #[test]
+    fn test_session_status_severity() {
+        let ordered = [
+            SessionStatus::Ok,
+            SessionStatus::Exited,
+            SessionStatus::Abnormal,
+            SessionStatus::Crashed,
+        ];
+
+        for pair in ordered.windows(2) {
+            assert!(pair[0].severity() < pair[1].severity());
+            assert_eq!(SessionStatus::max_severity(pair[0], pair[1]), pair[1]);
+            assert_eq!(SessionStatus::max_severity(pair[1], pair[0]), pair[1]);
+        }
+
+        assert_eq!(
+            SessionStatus::max_severity(SessionStatus::Ok, SessionStatus::Crashed),
+            SessionStatus::Crashed
+        );
+    }
+
+    #[test]
+    fn test_session_validate_errors() {
+        let json = r#"{
+  "started": "2020-02-07T14:16:00Z",
+  "errors": 18446744073709551615,
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let mut update = SessionUpdate::parse(json.as_bytes()).unwrap();
+        assert!(update.validate(DEFAULT_MAX_SESSION_ERRORS));
+        assert_eq!(update.errors, DEFAULT_MAX_SESSION_ERRORS);
+
+        update.errors = 42;
+        assert!(!update.validate(DEFAULT_MAX_SESSION_ERRORS));
+        assert_eq!(update.errors, 42);
+    }
+
+    #[test]
+    fn test_session_attributes_hash() {
+        fn hash(attributes: &SessionAttributes) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            attributes.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let attributes = SessionAttributes {
+            release: "sentry-test@1.0.0".to_owned(),
+            environment: Some("production".to_owned()),
+            ip_address: Some("127.0.0.1".parse().unwrap()),
+            user_agent: None,
+        };
+
+        let same = attributes.clone();
+        assert_eq!(attributes, same);
+        assert_eq!(hash(&attributes), hash(&same));
+
+        let mut other = attributes.clone();
+        other.ip_address = Some("::1".parse().unwrap());
+        assert_ne!(attributes, other);
+        assert_ne!(hash(&attributes), hash(&other));
+
+        let mut grouped = HashMap::new();
+        *grouped.entry(attributes).or_insert(0) += 1;
+        *grouped.entry(same).or_insert(0) += 1;
+        *grouped.entry(other).or_insert(0) += 1;
+        assert_eq!(grouped.len(), 2);
+    }
+
+    #[test]
+    fn test_session_environment_allowed() {
+        let allowlist = vec!["production".to_owned(), "staging".to_owned()];
+        let mut attributes = SessionAttributes {
+            release: "sentry-test@1.0.0".to_owned(),
+            environment: Some("production".to_owned()),
+            ip_address: None,
+            user_agent: None,
+        };
+        assert!(attributes.environment_allowed(&allowlist, false));
+
+        attributes.environment = Some("Production".to_owned());
+        assert!(!attributes.environment_allowed(&allowlist, true));
+
+        attributes.environment = Some("development".to_owned());
+        assert!(!attributes.environment_allowed(&allowlist, true));
+
+        attributes.environment = None;
+        assert!(attributes.environment_allowed(&allowlist, true));
+        assert!(!attributes.environment_allowed(&allowlist, false));
+    }
+
+    #[test]
+    fn test_session_environment_or_default() {
+        let mut attributes = SessionAttributes {
+            release: "sentry-test@1.0.0".to_owned(),
+            environment: Some("staging".to_owned()),
+            ip_address: None,
+            user_agent: None,
+        };
+        assert_eq!(attributes.environment_or_default("production"), "staging");
+
+        attributes.environment = None;
+        assert_eq!(
+            attributes.environment_or_default("production"),
+            "production"
+        );
+        assert_eq!(attributes.environment, None);
+    }
+
+    #[test]
+    fn test_session_anonymize_ip() {
+        let mut attributes = SessionAttributes {
+            release: "sentry-test@1.0.0".to_owned(),
+            environment: None,
+            ip_address: Some("192.168.1.42".parse().unwrap()),
+            user_agent: None,
+        };
+        attributes.anonymize_ip();
+        assert_eq!(attributes.ip_address, Some("192.168.1.0".parse().unwrap()));
+
+        attributes.ip_address = Some("2001:db8:85a3:8d3:1319:8a2e:370:7348".parse().unwrap());
+        attributes.anonymize_ip();
+        assert_eq!(
+            attributes.ip_address,
+            Some("2001:db8:85a3::".parse().unwrap())
+        );
+
+        attributes.ip_address = None;
+        attributes.anonymize_ip();
+        assert_eq!(attributes.ip_address, None);
+    }
+
+    #[test]
+    fn test_session_validate_times() {
+        let json = r#"{
+  "started": "2020-02-07T14:16:00Z",
+  "timestamp": "2020-02-07T15:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let mut update = SessionUpdate::parse(json.as_bytes()).unwrap();
+        assert_eq!(update.validate_times(), Ok(()));
+
+        std::mem::swap(&mut update.started, &mut update.timestamp);
+        assert_eq!(
+            update.validate_times(),
+            Err(SessionTimeError::TimestampBeforeStart)
+        );
+
+        update.started = Utc::now();
+        update.timestamp = update.started + Duration::hours(2);
+        assert_eq!(update.validate_times(), Err(SessionTimeError::InFuture));
+        assert_eq!(update.validate_times_with_skew(Duration::hours(3)), Ok(()));
+    }
+
+    #[test]
+    fn test_session_merge() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "seq": 1,
+  "init": true,
+  "timestamp": "2020-02-07T14:16:00Z",
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let mut update = SessionUpdate::parse(json.as_bytes()).unwrap();
+        let mut crashed = update.clone();
+        crashed.init = false;
+        crashed.sequence = 2;
+        crashed.timestamp = "2020-02-07T14:17:00Z".parse().unwrap();
+        crashed.duration = Some(60.0);
+        crashed.status = SessionStatus::Crashed;
+        crashed.errors = 1;
+
+        update.merge(&crashed);
+        assert_eq!(update.sequence, 2);
+        assert!(update.init);
+        assert_eq!(update.timestamp, crashed.timestamp);
+        assert!((update.duration.unwrap() - 60.0).abs() < f64::EPSILON);
+        assert_eq!(update.status, SessionStatus::Crashed);
+        assert_eq!(update.errors, 1);
+    }
+
+    #[test]
+    fn test_default_sequence() {
+        let first = default_sequence();
+        let second = default_sequence();
+        assert!(first > 0);
+        assert!(second >= first);
+    }
+
+    #[test]
+    fn test_dedup_sessions() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "seq": 1,
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let first = SessionUpdate::parse(json.as_bytes()).unwrap();
+        let mut second = first.clone();
+        second.sequence = 2;
+
+        let updates = vec![first.clone(), first.clone(), second.clone()];
+        let deduped: Vec<_> = dedup_sessions(updates, 10).collect();
+        assert_eq!(deduped, vec![first.clone(), second.clone()]);
+
+        // with a capacity of one, the first update is forgotten after the second
+        let updates = vec![first.clone(), second.clone(), first.clone()];
+        let deduped: Vec<_> = dedup_sessions(updates, 1).collect();
+        assert_eq!(deduped.len(), 3);
+
+        // a repeated duplicate stays remembered, even if it was seen first
+        let mut third = first.clone();
+        third.sequence = 3;
+        let updates = vec![
+            first.clone(),
+            second.clone(),
+            first.clone(),
+            third,
+            first.clone(),
+            second.clone(),
+        ];
+        let deduped: Vec<_> = dedup_sessions(updates, 2).collect();
+        assert_eq!(deduped.len(), 4);
+        assert_eq!(deduped[3], second);
+    }
+
+    #[test]
+    fn test_session_gap_detector() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "seq": 1,
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let mut update = SessionUpdate::parse(json.as_bytes()).unwrap();
+        let mut detector = SessionGapDetector::new(10);
+        assert_eq!(detector.observe(&update), None);
+
+        update.sequence = 2;
+        assert_eq!(detector.observe(&update), None);
+
+        update.sequence = 8;
+        assert_eq!(detector.observe(&update), Some(5));
+
+        // a late update does not report a gap
+        update.sequence = 5;
+        assert_eq!(detector.observe(&update), None);
+        update.sequence = 9;
+        assert_eq!(detector.observe(&update), None);
+    }
+
+    #[test]
+    fn test_session_gap_detector_eviction() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "seq": 1,
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let mut active = SessionUpdate::parse(json.as_bytes()).unwrap();
+        let mut detector = SessionGapDetector::new(2);
+        assert_eq!(detector.observe(&active), None);
+
+        // the active session keeps sending updates while other sessions come and go. Each update
+        // skips one sequence number, which is only detected if the session is still tracked.
+        for _ in 0..4 {
+            let mut other = active.clone();
+            other.session_id = Uuid::new_v4();
+            assert_eq!(detector.observe(&other), None);
+
+            active.sequence += 2;
+            assert_eq!(detector.observe(&active), Some(1));
+        }
+    }
+
+    #[test]
+    fn test_session_counters() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "seq": 1,
+  "init": true,
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let init = SessionUpdate::parse(json.as_bytes()).unwrap();
+        let mut crashed = init.clone();
+        crashed.init = false;
+        crashed.sequence = 2;
+        crashed.status = SessionStatus::Crashed;
+        crashed.errors = 1;
+
+        let mut counters = SessionCounters::new();
+        counters.record(&init);
+        assert_eq!(counters.snapshot().ok, 1);
+
+        counters.record(&crashed);
+        // an outdated retransmission of the initial update is ignored
+        counters.record(&init);
+
+        let counts = counters.snapshot();
+        assert_eq!(
+            counts,
+            SessionCounts {
+                ok: 0,
+                exited: 0,
+                crashed: 1,
+                abnormal: 0,
+                errors: 1,
+                errored: 1,
+            }
+        );
+        assert_eq!(counts.get(SessionStatus::Crashed), 1);
+        assert_eq!(counts.total(), 1);
+    }
+
+    #[test]
+    fn test_session_aggregate() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "seq": 1,
+  "init": true,
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let init = SessionUpdate::parse(json.as_bytes()).unwrap();
+        let mut crashed = init.clone();
+        crashed.init = false;
+        crashed.sequence = 2;
+        crashed.status = SessionStatus::Crashed;
+        crashed.errors = 1;
+
+        let mut other = init.clone();
+        other.session_id = Uuid::new_v4();
+        other.status = SessionStatus::Exited;
+
+        let aggregate = SessionAggregate::from_updates_iter(&[init, crashed, other]);
+        assert_eq!(aggregate.total(), 2);
+        assert_eq!(aggregate.crashed(), 1);
+        assert_eq!(aggregate.abnormal(), 0);
+        assert_eq!(aggregate.errored(), 1);
+        assert!((aggregate.crash_free_rate() - 0.5).abs() < f64::EPSILON);
+
+        assert!((SessionAggregate::new().crash_free_rate() - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_session_parse_ndjson() {
+        let ndjson = br#"{"sid":"8333339f-5675-4f89-a9a0-1c935255ab58","started":"2020-02-07T14:16:00Z","attrs":{"release":"sentry-test@1.0.0"}}
+
+{"sid":"not-a-uuid","started":"2020-02-07T14:16:00Z","attrs":{"release":"sentry-test@1.0.0"}}
+{"sid":"1bca4cbe-a0aa-4ce6-9e66-c4f9e8b5a1b4","started":"2020-02-07T14:16:00Z","attrs":{"release":"sentry-test@1.0.0"}}
+"#;
+
+        let results = SessionUpdate::parse_ndjson(&ndjson[..]).collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+
+        let first = results[0].as_ref().unwrap();
+        assert_eq!(
+            first.session_id,
+            "8333339f-5675-4f89-a9a0-1c935255ab58".parse().unwrap()
+        );
+
+        assert!(results[1].is_err());
+
+        let last = results[2].as_ref().unwrap();
+        assert_eq!(
+            last.session_id,
+            "1bca4cbe-a0aa-4ce6-9e66-c4f9e8b5a1b4".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_session_hashed_distinct_id() {
+        let json = r#"{
+  "did": "foobarbaz",
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0"
+  }
+}"#;
+
+        let mut update = SessionUpdate::parse(json.as_bytes()).unwrap();
+        let hashed = update.hashed_distinct_id(b"salt").unwrap();
+        assert_eq!(
+            hashed,
+            "bca997c8afb7006983e63eeb9c6d152cc7150f894e095248a8d7d7c86c473b36"
+        );
+        assert_eq!(update.hashed_distinct_id(b"salt").unwrap(), hashed);
+        assert_ne!(update.hashed_distinct_id(b"pepper").unwrap(), hashed);
+        assert_eq!(update.distinct_id.as_deref(), Some("foobarbaz"));
+
+        update.distinct_id = None;
+        assert_eq!(update.hashed_distinct_id(b"salt"), None);
+    }
+
+    #[test]
+    fn test_session_anonymize() {
+        let json = r#"{
+  "did": "foobarbaz",
+  "started": "2020-02-07T14:16:00Z",
+  "attrs": {
+    "release": "sentry-test@1.0.0",
+    "ip_address": "192.168.1.42",
+    "user_agent": "Firefox/72.0"
+  }
+}"#;
+
+        let original = SessionUpdate::parse(json.as_bytes()).unwrap();
+
+        let mut update = original.clone();
+        update.anonymize(&AnonymizeOptions::default());
+        assert_eq!(update.distinct_id, None);
+        assert_eq!(
+            update.attributes.ip_address,
+            Some("192.168.1.0".parse().unwrap())
+        );
+        assert_eq!(
+            update.attributes.user_agent.as_deref(),
+            Some("Firefox/72.0")
+        );
+
+        let options = AnonymizeOptions {
+            distinct_id_salt: Some(b"salt".to_vec()),
+            anonymize_ip: false,
+            remove_user_agent: true,
+        };
+        let mut update = original.clone();
+        update.anonymize(&options);
+        assert_eq!(update.distinct_id, original.hashed_distinct_id(b"salt"));
+        assert_eq!(update.attributes.ip_address, original.attributes.ip_address);
+        assert_eq!(update.attributes.user_agent, None);
+    }
+
+    #[test]
+    #[cfg(feature = "jsonschema")]
+    fn test_session_json_schema() {
+        let json = r#"{
+  "sid": "8333339f-5675-4f89-a9a0-1c935255ab58",
+  "did": "foobarbaz",
+  "seq": 42,
+  "init": true,
+  "timestamp": "2020-02-07T15:17:00Z",
+  "started": "2020-02-07T14:16:00Z",
+  "duration": 1947.49,
+  "status": "exited",
+  "errors": 0,
+  "attrs": {
+    "release": "sentry-test@1.0.0",
+    "environment": "production",
+    "ip_address": "::1",
+    "user_agent": "Firefox/72.0"
+  },
+  "sdk": {
+    "name": "sentry.javascript.browser",
+    "version": "5.27.0"
+  }
+}"#;
+
+        let schema = serde_json::to_value(crate::protocol::session_json_schema()).unwrap();
+        let mut scope = valico::json_schema::Scope::new();
+        let schema = scope.compile_and_return(schema, false).unwrap();
+
+        let mut value: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert!(schema.validate(&value).is_valid());
+
+        value["status"] = "unknown".into();
+        assert!(!schema.validate(&value).is_valid());
+        value["status"] = "crashed".into();
+
+        value["attrs"].as_object_mut().unwrap().remove("release");
+        assert!(!schema.validate(&value).is_valid());
+
+        value.as_object_mut().unwrap().remove("attrs");
+        assert!(!schema.validate(&value).is_valid());
+    }
+
+    #[test]
+    #[cfg(feature = "jsonschema")]
+    fn test_session_json_schema_stable() {
+        let first = serde_json::to_string(&crate::protocol::session_json_schema()).unwrap();
+        let second = serde_json::to_string(&crate::protocol::session_json_schema()).unwrap();
+        assert_eq!(first, second);
+    }
 }
@@ -1,40 +1,10 @@
-use regex::Regex;
-
-use crate::processor::{ProcessValue, ProcessingState, Processor};
+use crate::processor::{apply_trim, ProcessValue, ProcessingState, Processor};
 use crate::types::{
     Array, Empty, Error, ErrorKind, Meta, Object, ProcessingAction, ProcessingResult,
 };
 
 pub struct SchemaProcessor;
 
-macro_rules! declare_used_field_regexes {
-    ($($ident:ident: $regex:expr),* $(,)*) => {
-        fn get_regex(name: &'static str) -> &'static Regex {
-            lazy_static::lazy_static! {
-                $(
-                    static ref $ident: Regex = Regex::new($regex).unwrap();
-                )*
-            };
-
-            match name {
-                $($regex => &$ident, )*
-                _ => panic!("Please declare Regex {} using declare_used_field_regexes.", name),
-            }
-        }
-    }
-}
-
-// Pre-built list of regexes for max performance. The identifier in front is arbitrary, but needs
-// to be unique.
-declare_used_field_regexes![
-    A: r"^[^\r\n\f\t/]*\z",
-    B: r"^[^\r\n\x0C/]+$",
-    C: r"^[^\r\n]*\z",
-    D: r"^[a-zA-Z0-9_\.:-]+\z",
-    E: r"^\s*[a-zA-Z0-9_.-]*\s*$",
-    F: r"^[^\n]+\z",
-];
-
 impl Processor for SchemaProcessor {
     fn process_string(
         &mut self,
@@ -95,12 +65,7 @@ fn value_trim_whitespace(
     _meta: &mut Meta,
     state: &ProcessingState<'_>,
 ) -> ProcessingResult {
-    if state.attrs().trim_whitespace {
-        let new_value = value.trim().to_owned();
-        value.clear();
-        value.push_str(&new_value);
-    }
-
+    apply_trim(value, state.attrs());
     Ok(())
 }
 
@@ -125,11 +90,9 @@ fn verify_value_pattern(
     meta: &mut Meta,
     state: &ProcessingState<'_>,
 ) -> ProcessingResult {
-    if let Some(ref regex_string) = state.attrs().match_regex {
-        if !get_regex(regex_string).is_match(value) {
-            meta.add_error(Error::invalid("invalid characters in string"));
-            return Err(ProcessingAction::DeleteValueSoft);
-        }
+    if let Err(error) = state.attrs().validate_value(value) {
+        meta.add_error(Error::invalid(error));
+        return Err(ProcessingAction::DeleteValueSoft);
     }
 
     Ok(())
@@ -18,7 +18,7 @@ use relay_general::pii::{PiiAttachmentsProcessor, PiiProcessor};
 use relay_general::processor::{process_value, ProcessingState};
 use relay_general::protocol::{
     Breadcrumb, Csp, Event, EventId, EventType, ExpectCt, ExpectStaple, Hpkp, LenientString,
-    Metrics, SecurityReportType, SessionUpdate, Timestamp, Values,
+    Metrics, SecurityReportType, SessionUpdate, Timestamp, Values, DEFAULT_MAX_SESSION_ERRORS,
 };
 use relay_general::store::ClockDriftProcessor;
 use relay_general::types::{Annotated, Array, Object, ProcessingAction, Value};
@@ -328,6 +328,11 @@ impl EventProcessor {
                 changed = true;
             }
 
+            if session.validate(DEFAULT_MAX_SESSION_ERRORS) {
+                log::trace!("clamping session error count to {}", session.errors);
+                changed = true;
+            }
+
             if session.timestamp < session.started {
                 log::trace!("fixing session timestamp to {}", session.timestamp);
                 session.timestamp = session.started;